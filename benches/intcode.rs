@@ -0,0 +1,444 @@
+use advent_of_code_2019::cpu::cache::DecodedProgram;
+use advent_of_code_2019::cpu::{parse_program, Execution, ExecutionState, IntCode, Memory};
+use permutohedron::LexicalPermutation;
+use std::time::{Duration, Instant};
+
+/// Compares the interpreter against the original one it grew out of, and a program decoded once up
+/// front against decoding every instruction, run with `cargo bench`
+fn main() {
+    let day_2 = Program::new(include_str!("../src/bin/2_input.txt"));
+    let day_7 = Program::new(include_str!("../src/bin/7_input.txt"));
+    let day_9 = Program::new(include_str!("../src/bin/9_input.txt"));
+    let day_25 = Program::new(include_str!("../src/bin/25_input.txt"));
+
+    compare("day 2 part 2", |kind| day_2_part_2(&day_2, kind));
+    compare("day 7 part 2", |kind| day_7_part_2(&day_7, kind));
+    compare("day 9 part 2", |kind| day_9_part_2(&day_9, kind));
+    compare("day 25 intro", |kind| day_25_intro(&day_25, kind));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Baseline,
+    Uncached,
+    Cached,
+}
+
+const KINDS: [Kind; 3] = [Kind::Baseline, Kind::Uncached, Kind::Cached];
+
+/// A puzzle input, decoded ahead of time for the cached runs
+struct Program {
+    memory: Memory,
+    decoded: DecodedProgram,
+}
+
+impl Program {
+    fn new(input: &str) -> Program {
+        let memory = parse_program(input);
+        Program {
+            decoded: DecodedProgram::new(memory.clone()),
+            memory,
+        }
+    }
+}
+
+/// Just enough of an interpreter to run the puzzles with
+trait Interpreter {
+    fn new(program: &Program, input: Vec<IntCode>, kind: Kind) -> Self;
+
+    fn run(&mut self) -> ExecutionState;
+
+    fn get(&self, address: usize) -> IntCode;
+
+    fn set(&mut self, address: usize, value: IntCode);
+
+    fn push_input(&mut self, value: IntCode);
+
+    fn pop_output(&mut self) -> Option<IntCode>;
+}
+
+impl Interpreter for Execution {
+    fn new(program: &Program, input: Vec<IntCode>, kind: Kind) -> Self {
+        match kind {
+            Kind::Cached => program.decoded.execution_input(input),
+            _ => Execution::new_input(program.memory.clone(), input),
+        }
+    }
+
+    fn run(&mut self) -> ExecutionState {
+        Execution::run(self).expect("This should always work")
+    }
+
+    fn get(&self, address: usize) -> IntCode {
+        self[address]
+    }
+
+    fn set(&mut self, address: usize, value: IntCode) {
+        self[address] = value;
+    }
+
+    fn push_input(&mut self, value: IntCode) {
+        self.input.push_back(value);
+    }
+
+    fn pop_output(&mut self) -> Option<IntCode> {
+        self.output.pop_front()
+    }
+}
+
+impl Interpreter for baseline::Execution {
+    fn new(program: &Program, input: Vec<IntCode>, _kind: Kind) -> Self {
+        baseline::Execution::new_input(program.memory.clone(), input)
+    }
+
+    fn run(&mut self) -> ExecutionState {
+        baseline::Execution::run(self)
+    }
+
+    fn get(&self, address: usize) -> IntCode {
+        self[address]
+    }
+
+    fn set(&mut self, address: usize, value: IntCode) {
+        self[address] = value;
+    }
+
+    fn push_input(&mut self, value: IntCode) {
+        self.input.push_back(value);
+    }
+
+    fn pop_output(&mut self) -> Option<IntCode> {
+        self.output.pop_front()
+    }
+}
+
+fn day_2_part_2(program: &Program, kind: Kind) -> IntCode {
+    match kind {
+        Kind::Baseline => find_noun_and_verb::<baseline::Execution>(program, kind),
+        _ => find_noun_and_verb::<Execution>(program, kind),
+    }
+}
+
+fn find_noun_and_verb<E: Interpreter>(program: &Program, kind: Kind) -> IntCode {
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            let mut execution = E::new(program, vec![], kind);
+            execution.set(1, noun);
+            execution.set(2, verb);
+            execution.run();
+
+            if execution.get(0) == 19_690_720 {
+                return 100 * noun + verb;
+            }
+        }
+    }
+
+    panic!("No noun and verb produce our goal");
+}
+
+fn day_7_part_2(program: &Program, kind: Kind) -> IntCode {
+    match kind {
+        Kind::Baseline => max_thrust::<baseline::Execution>(program, kind),
+        _ => max_thrust::<Execution>(program, kind),
+    }
+}
+
+fn max_thrust<E: Interpreter>(program: &Program, kind: Kind) -> IntCode {
+    let mut max_thrust = 0;
+    let mut phase_settings = [5, 6, 7, 8, 9];
+    loop {
+        let mut executions: Vec<E> = phase_settings
+            .iter()
+            .map(|&phase| E::new(program, vec![phase], kind))
+            .collect();
+
+        let mut output = 0;
+        let mut state = ExecutionState::Running;
+        while state != ExecutionState::Halted {
+            for execution in executions.iter_mut() {
+                execution.push_input(output);
+                state = execution.run();
+                output = execution.pop_output().expect("Expected an output");
+            }
+        }
+        max_thrust = max_thrust.max(output);
+
+        if !phase_settings.next_permutation() {
+            return max_thrust;
+        }
+    }
+}
+
+fn day_9_part_2(program: &Program, kind: Kind) -> IntCode {
+    match kind {
+        Kind::Baseline => last_output::<baseline::Execution>(program, vec![2], kind),
+        _ => last_output::<Execution>(program, vec![2], kind),
+    }
+}
+
+/// Runs up to the first prompt, which is mostly printing the intro
+fn day_25_intro(program: &Program, kind: Kind) -> IntCode {
+    match kind {
+        Kind::Baseline => last_output::<baseline::Execution>(program, vec![], kind),
+        _ => last_output::<Execution>(program, vec![], kind),
+    }
+}
+
+fn last_output<E: Interpreter>(program: &Program, input: Vec<IntCode>, kind: Kind) -> IntCode {
+    let mut execution = E::new(program, input, kind);
+    execution.run();
+
+    let mut last = None;
+    while let Some(output) = execution.pop_output() {
+        last = Some(output);
+    }
+
+    last.expect("Expected an output")
+}
+
+/// Times each kind of interpreter, taking turns so they all see the same noise, and reports the
+/// fastest run of each
+fn compare<F: Fn(Kind) -> IntCode>(name: &str, run: F) {
+    const RUNS: usize = 20;
+
+    let expected = run(Kind::Baseline);
+    for &kind in KINDS.iter() {
+        assert_eq!(
+            run(kind),
+            expected,
+            "{} {:?} gives the wrong answer",
+            name,
+            kind
+        );
+    }
+
+    let mut fastest = [Duration::MAX; 3];
+    for _ in 0..RUNS {
+        for (i, &kind) in KINDS.iter().enumerate() {
+            fastest[i] = fastest[i].min(time(|| run(kind)));
+        }
+    }
+    let [baseline, uncached, cached] = fastest;
+
+    println!(
+        "{:<14} baseline: {:>9.3?} uncached: {:>9.3?} ({:.2}x) cached: {:>9.3?} ({:.2}x)",
+        name,
+        baseline,
+        uncached,
+        baseline.as_secs_f64() / uncached.as_secs_f64(),
+        cached,
+        baseline.as_secs_f64() / cached.as_secs_f64(),
+    );
+}
+
+fn time<F: Fn() -> IntCode>(f: F) -> Duration {
+    let start = Instant::now();
+    f();
+
+    start.elapsed()
+}
+
+/// The interpreter as it was before it grew any features, to keep us honest about what they cost
+mod baseline {
+    use advent_of_code_2019::cpu::{ExecutionState, IntCode, Memory};
+    use std::collections::VecDeque;
+    use std::ops::{Index, IndexMut};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum OpCode {
+        Add,
+        Mul,
+        Input,
+        Output,
+        JumpIfTrue,
+        JumpIfFalse,
+        LessThan,
+        Equals,
+        AdjustBase,
+        Halt,
+    }
+
+    impl OpCode {
+        fn new(instruction: IntCode) -> OpCode {
+            match instruction % 100 {
+                1 => OpCode::Add,
+                2 => OpCode::Mul,
+                3 => OpCode::Input,
+                4 => OpCode::Output,
+                5 => OpCode::JumpIfTrue,
+                6 => OpCode::JumpIfFalse,
+                7 => OpCode::LessThan,
+                8 => OpCode::Equals,
+                9 => OpCode::AdjustBase,
+                99 => OpCode::Halt,
+                _ => panic!("Invalid op code {}", instruction),
+            }
+        }
+
+        fn instruction_size(&self) -> usize {
+            match self {
+                OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => 4,
+                OpCode::Input | OpCode::Output | OpCode::AdjustBase => 2,
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse => 3,
+                OpCode::Halt => 0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Mode {
+        Position,
+        Immediate,
+        Relative,
+    }
+
+    impl Mode {
+        fn new(int_code: IntCode) -> Mode {
+            match int_code {
+                0 => Mode::Position,
+                1 => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => panic!("Invalid mode {}", int_code),
+            }
+        }
+    }
+
+    pub struct Execution {
+        ip: usize,
+        relative_base: usize,
+        memory: Memory,
+        pub input: VecDeque<IntCode>,
+        pub output: VecDeque<IntCode>,
+    }
+
+    impl Execution {
+        pub fn new_input(memory: Memory, input: Memory) -> Execution {
+            Execution {
+                ip: 0,
+                relative_base: 0,
+                memory,
+                input: input.into(),
+                output: VecDeque::new(),
+            }
+        }
+
+        pub fn run(&mut self) -> ExecutionState {
+            let mut state = self.step();
+            while state == ExecutionState::Running {
+                state = self.step();
+            }
+
+            state
+        }
+
+        fn step(&mut self) -> ExecutionState {
+            let instruction = self.memory[self.ip];
+            let op_code = OpCode::new(instruction);
+            let modes = [
+                Mode::new((instruction / 100) % 10),
+                Mode::new((instruction / 1000) % 10),
+                Mode::new((instruction / 10000) % 10),
+            ];
+            let mut ip_offset = op_code.instruction_size();
+
+            let state = match op_code {
+                OpCode::Add => {
+                    *self.write(&modes, 2) = self.read(&modes, 0) + self.read(&modes, 1);
+                    ExecutionState::Running
+                }
+                OpCode::Mul => {
+                    *self.write(&modes, 2) = self.read(&modes, 0) * self.read(&modes, 1);
+                    ExecutionState::Running
+                }
+                OpCode::Input => match self.input.pop_front() {
+                    Some(i) => {
+                        *self.write(&modes, 0) = i;
+                        ExecutionState::Running
+                    }
+                    None => ExecutionState::NeedsInput,
+                },
+                OpCode::Output => {
+                    let output = self.read(&modes, 0);
+                    self.output.push_back(output);
+                    ExecutionState::Running
+                }
+                OpCode::JumpIfTrue => {
+                    if self.read(&modes, 0) != 0 {
+                        self.ip = self.read(&modes, 1) as usize;
+                        ip_offset = 0;
+                    }
+                    ExecutionState::Running
+                }
+                OpCode::JumpIfFalse => {
+                    if self.read(&modes, 0) == 0 {
+                        self.ip = self.read(&modes, 1) as usize;
+                        ip_offset = 0;
+                    }
+                    ExecutionState::Running
+                }
+                OpCode::LessThan => {
+                    let less = self.read(&modes, 0) < self.read(&modes, 1);
+                    *self.write(&modes, 2) = less as IntCode;
+                    ExecutionState::Running
+                }
+                OpCode::Equals => {
+                    let equal = self.read(&modes, 0) == self.read(&modes, 1);
+                    *self.write(&modes, 2) = equal as IntCode;
+                    ExecutionState::Running
+                }
+                OpCode::AdjustBase => {
+                    self.relative_base =
+                        ((self.relative_base as IntCode) + self.read(&modes, 0)) as usize;
+                    ExecutionState::Running
+                }
+                OpCode::Halt => ExecutionState::Halted,
+            };
+
+            if ExecutionState::Running == state {
+                self.ip += ip_offset;
+            }
+
+            state
+        }
+
+        fn read(&self, modes: &[Mode; 3], offset: usize) -> IntCode {
+            let value = self[self.ip + offset + 1];
+            match modes[offset] {
+                Mode::Position => self[value as usize],
+                Mode::Immediate => value,
+                Mode::Relative => self[(self.relative_base as IntCode + value) as usize],
+            }
+        }
+
+        fn write(&mut self, modes: &[Mode; 3], offset: usize) -> &mut IntCode {
+            let value = self[self.ip + offset + 1];
+            match modes[offset] {
+                Mode::Position => &mut self[value as usize],
+                Mode::Immediate => panic!("We should never write in immediate mode"),
+                Mode::Relative => {
+                    let address = (self.relative_base as IntCode + value) as usize;
+                    &mut self[address]
+                }
+            }
+        }
+    }
+
+    impl Index<usize> for Execution {
+        type Output = IntCode;
+
+        fn index(&self, address: usize) -> &Self::Output {
+            // memory is initialized to zero
+            self.memory.get(address).unwrap_or(&0)
+        }
+    }
+
+    impl IndexMut<usize> for Execution {
+        fn index_mut(&mut self, address: usize) -> &mut Self::Output {
+            if address >= self.memory.len() {
+                self.memory.resize(address + 1, 0);
+            }
+
+            &mut self.memory[address]
+        }
+    }
+}
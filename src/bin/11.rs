@@ -147,25 +147,28 @@ fn step_robot(execution: &mut Execution, panel: PanelColor) -> Option<(PanelColo
 
     execution.input.push_back(input);
 
-    let state = execution.run().expect("CPU Error");
-
-    if state == ExecutionState::Halted && execution.output.is_empty() {
-        None
-    } else {
-        let paint_color = match execution.output.pop_front().expect("CPU Error") {
-            0 => PanelColor::Black,
-            1 => PanelColor::White,
+    match execution.run_until_outputs(2).expect("CPU Error") {
+        (output, ExecutionState::Halted) if output.is_empty() => None,
+        (output, _) => {
+            let (paint, rotate) = match output.as_slice() {
+                [paint, rotate] => (*paint, *rotate),
+                _ => panic!("Invalid output"),
+            };
 
-            _ => panic!("Invalid output"),
-        };
+            let paint_color = match paint {
+                0 => PanelColor::Black,
+                1 => PanelColor::White,
+                _ => panic!("Invalid output"),
+            };
 
-        let rotate_direction = match execution.output.pop_front().expect("CPU Error") {
-            0 => Rotate::Left,
-            1 => Rotate::Right,
-            _ => panic!("Invalid output"),
-        };
+            let rotate_direction = match rotate {
+                0 => Rotate::Left,
+                1 => Rotate::Right,
+                _ => panic!("Invalid output"),
+            };
 
-        Some((paint_color, rotate_direction))
+            Some((paint_color, rotate_direction))
+        }
     }
 }
 
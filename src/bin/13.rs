@@ -1,9 +1,14 @@
 use advent_of_code_2019::coordinates::Grid;
-use advent_of_code_2019::cpu::{parse_program, Execution, ExecutionState, Memory};
+use advent_of_code_2019::cpu::io::Input;
+use advent_of_code_2019::cpu::{
+    parse_program, CPUError, Execution, ExecutionState, IntCode, Memory,
+};
 use advent_of_code_2019::problem::{run, Problem, ProblemState};
 use advent_of_code_2019::thirteen::*;
 use env_logger::Env;
 use log::Level;
+use std::collections::VecDeque;
+use std::io;
 
 struct Thirteen {}
 
@@ -44,10 +49,11 @@ impl Problem for Thirteen {
         // pay 2 "quarters" for our game
         paid_program[0] = 2;
 
-        let mut execution = Execution::new_input(
-            paid_program,
-            parse_program(include_str!("../thirteen/13_perfect_game.txt")),
-        );
+        // play the recorded game, then hand the joystick over to whoever is at stdin
+        let joystick = Joystick::new(parse_program(include_str!(
+            "../thirteen/13_perfect_game.txt"
+        )));
+        let mut execution = Execution::with_io(paid_program, joystick, VecDeque::new());
 
         while execution.run().expect("No errors") != ExecutionState::Halted {
             let (score, _, _) = read_output(&mut execution, &mut screen);
@@ -56,26 +62,6 @@ impl Problem for Thirteen {
                 screen.print_top_down();
                 println!("score: {}", score);
             }
-
-            let joystick_direction;
-            loop {
-                use std::io;
-
-                let mut input = String::new();
-                io::stdin()
-                    .read_line(&mut input)
-                    .expect("Please enter something");
-
-                joystick_direction = match input.trim() {
-                    "a" => -1,
-                    "d" => 1,
-                    "" | "s" => 0,
-                    _ => continue,
-                };
-                break;
-            }
-
-            execution.input.push_back(joystick_direction);
         }
 
         let (score, _, _) = read_output(&mut execution, &mut screen);
@@ -88,6 +74,50 @@ impl Problem for Thirteen {
     }
 }
 
+/// Plays back recorded moves, then reads `a` (left), `d` (right) or `s` (stay) from stdin. Before
+/// each read of stdin it runs dry once, so the game stops to let us draw the screen
+struct Joystick {
+    recorded: VecDeque<IntCode>,
+    drawn: bool,
+}
+
+impl Joystick {
+    fn new(recorded: Memory) -> Joystick {
+        Joystick {
+            recorded: recorded.into(),
+            drawn: false,
+        }
+    }
+}
+
+impl Input for Joystick {
+    fn read(&mut self) -> Result<Option<IntCode>, CPUError> {
+        if let Some(tilt) = self.recorded.pop_front() {
+            return Ok(Some(tilt));
+        }
+        if !self.drawn {
+            self.drawn = true;
+            return Ok(None);
+        }
+        self.drawn = false;
+
+        loop {
+            let mut line = String::new();
+            // once stdin runs out the joystick stays put
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Ok(Some(0));
+            }
+
+            match line.trim() {
+                "a" => return Ok(Some(-1)),
+                "d" => return Ok(Some(1)),
+                "" | "s" => return Ok(Some(0)),
+                _ => continue,
+            }
+        }
+    }
+}
+
 fn main() {
     env_logger::init_from_env(Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "warn"));
 
@@ -1,3 +1,4 @@
+use advent_of_code_2019::cpu::solver::{Affine, Goal, Solver};
 use advent_of_code_2019::cpu::{parse_program, Execution, Memory};
 use advent_of_code_2019::example;
 use advent_of_code_2019::problem::{run, Problem, ProblemState, RunFor};
@@ -29,22 +30,16 @@ impl Problem for Two {
     fn part_2(input: &Self::Input, state: &ProblemState<Self::Extra>) -> Option<String> {
         let goal = if state.is_example { 1202 } else { 19_690_720 };
 
-        let mut result = 0;
-        'outer: for noun in 0..=99 {
-            for verb in 0..=99 {
-                let mut execution: Execution = input.clone().into();
-                execution[1] = noun;
-                execution[2] = verb;
-
-                execution.run().expect("This should always work");
-                if goal == execution[0] {
-                    result = 100 * noun + verb;
-                    break 'outer;
-                }
-            }
-        }
+        let solver = Solver::new(input.clone()).cell(1, 0..=99).cell(2, 0..=99);
+        let first_cell = |execution: &Execution| execution[0];
+
+        // in the inputs we've seen the output moves linearly with the noun and verb. Affine checks
+        // that against a few sample runs and searches every pair if it doesn't hold
+        let values = solver
+            .solve(&Affine::new(), &Goal::Value(&first_cell, goal))
+            .expect("100 * 100 pairs fit in a usize")?;
 
-        Some(format!("{}", result))
+        Some(format!("{}", 100 * values[0] + values[1]))
     }
 
     fn problem_number() -> usize {
@@ -1,10 +1,7 @@
+use advent_of_code_2019::cpu::ascii::AsciiConsole;
 use advent_of_code_2019::cpu::{parse_program, Execution, IntCode, Memory};
 use advent_of_code_2019::problem::{run, Problem, ProblemState};
 use env_logger::Env;
-use log::Level;
-use std::collections::VecDeque;
-use std::convert::TryFrom;
-use std::mem;
 
 struct TwentyOne {}
 
@@ -56,49 +53,15 @@ impl Problem for TwentyOne {
 }
 
 fn run_springdroid(script: &str, program: &[IntCode]) -> Option<String> {
-    let mut spring_script = compile_spring_script(script);
-
-    let mut execution = Execution::new(program.to_owned());
-    execution.input.append(&mut spring_script);
-
-    execution.run().expect("The program should work");
-
-    process_output(&mut execution).map(|out| out.to_string())
-}
-
-fn process_output(execution: &mut Execution) -> Option<IntCode> {
-    let mut damage = None;
-
-    let mut output = String::with_capacity(execution.output.len());
-    for out in mem::replace(&mut execution.output, VecDeque::new()) {
-        match u8::try_from(out).ok().map(|n| n as char) {
-            Some(ascii) => {
-                if log::log_enabled!(Level::Info) {
-                    output.push(ascii)
-                }
-            }
-            None => damage = Some(out),
-        }
+    let mut console = AsciiConsole::new(Execution::new(program.to_owned()));
+    for row in script.lines().map(str::trim).filter(|row| !row.is_empty()) {
+        console.send_line(row);
     }
 
-    log::info!("{}", output);
-
-    damage
-}
+    let response = console.read().expect("The program should work");
+    log::info!("{}", response.text);
 
-fn compile_spring_script(s: &str) -> VecDeque<IntCode> {
-    s.trim()
-        .split('\n')
-        .filter_map(|mut row| {
-            row = row.trim();
-            if row.is_empty() {
-                None
-            } else {
-                Some(format!("{}\n", row))
-            }
-        })
-        .flat_map(|s| s.chars().map(|c| (c as IntCode)).collect::<Vec<_>>())
-        .collect()
+    response.values.last().map(|damage| damage.to_string())
 }
 
 fn main() {
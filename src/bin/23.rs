@@ -1,45 +1,29 @@
+use advent_of_code_2019::cpu::scheduler::{Link, Scheduler, SchedulerState};
 use advent_of_code_2019::cpu::{parse_program, Execution, IntCode, Memory};
 use advent_of_code_2019::problem::{run, Problem, ProblemState};
 use env_logger::Env;
-use std::collections::VecDeque;
-use std::mem;
 
-struct TwentyThree {}
-
-#[derive(Debug, Clone)]
-struct Computer {
-    address: IntCode,
-    net_buffer: VecDeque<IntCode>,
-    execution: Execution,
-}
+const NAT: IntCode = 255;
 
-impl Computer {
-    fn new(address: IntCode, program: &[IntCode]) -> Computer {
-        let mut execution = Execution::new(program.to_owned());
-        execution.input.push_back(address);
-
-        Computer {
-            address,
-            net_buffer: VecDeque::new(),
-            execution,
-        }
-    }
+struct TwentyThree {}
 
-    fn run(&mut self) -> VecDeque<IntCode> {
-        if self.net_buffer.is_empty() {
+fn network(program: &[IntCode]) -> Scheduler {
+    let mut scheduler = Scheduler::new();
+
+    // assign addresses and start our computers
+    for address in 0..50 {
+        let computer = scheduler.add(Execution::new_input(
+            program.to_owned(),
+            vec![address as IntCode],
+        ));
+        scheduler
+            .link(computer, Link::Packets { size: 3 })
             // let the computer know we don't have anything for it
-            self.execution.input.push_back(-1);
-        } else {
-            self.execution.input.append(&mut self.net_buffer);
-        }
-
-        self.execution.run().expect("The network never stops");
-
-        let mut output = VecDeque::new();
-        mem::swap(&mut output, &mut self.execution.output);
-
-        output
+            .and_then(|scheduler| scheduler.set_idle_input(computer, -1))
+            .expect("Our computers are all there");
     }
+
+    scheduler
 }
 
 impl Problem for TwentyThree {
@@ -51,78 +35,48 @@ impl Problem for TwentyThree {
     }
 
     fn part_1(program: &Self::Input, _state: &ProblemState<Self::Extra>) -> Option<String> {
-        let mut network = Vec::with_capacity(50);
+        let mut network = network(program);
 
-        // assign addresses and start our computers
-        for i in 0..50 {
-            network.push(Computer::new(i as IntCode, program));
-        }
+        loop {
+            network.round().expect("The network never stops");
 
-        let final_y;
-        'outer: loop {
-            for i in 0..50 {
-                let mut output = network[i].run();
-                while !output.is_empty() {
-                    let destination = output.pop_front().unwrap();
-                    let x = output.pop_front().unwrap();
-                    let y = output.pop_front().unwrap();
-
-                    if destination == 255 {
-                        final_y = y;
-                        break 'outer;
-                    } else {
-                        network[destination as usize].execution.input.push_back(x);
-                        network[destination as usize].execution.input.push_back(y);
-                    }
-                }
+            if let Some(packet) = network
+                .take_unrouted()
+                .into_iter()
+                .find(|packet| packet.destination == NAT)
+            {
+                return Some(packet.payload[1].to_string());
             }
         }
-
-        Some(final_y.to_string())
     }
 
     fn part_2(program: &Self::Input, _state: &ProblemState<Self::Extra>) -> Option<String> {
-        let mut network = Vec::with_capacity(50);
-
-        // assign addresses and start our computers
-        for i in 0..50 {
-            network.push(Computer::new(i as IntCode, program));
-        }
+        let mut network = network(program);
 
         let mut last_nat_y = 0;
-        let mut nat = (0, 0);
-        let final_y;
-        'outer: loop {
-            let mut idle = true;
-            for i in 0..50 {
-                let mut output = network[i].run();
-                while !output.is_empty() {
-                    idle = false;
-                    let destination = output.pop_front().unwrap();
-                    let x = output.pop_front().unwrap();
-                    let y = output.pop_front().unwrap();
-
-                    if destination == 255 {
-                        nat = (x, y);
-                    } else {
-                        network[destination as usize].execution.input.push_back(x);
-                        network[destination as usize].execution.input.push_back(y);
-                    }
+        let mut nat = vec![0, 0];
+        loop {
+            let state = network.round().expect("The network never stops");
+
+            for packet in network.take_unrouted() {
+                if packet.destination == NAT {
+                    nat = packet.payload;
                 }
             }
 
-            if idle {
-                if last_nat_y == nat.1 {
-                    final_y = nat.1;
-                    break 'outer;
+            if state == SchedulerState::Idle {
+                if last_nat_y == nat[1] {
+                    return Some(nat[1].to_string());
                 }
-                network[0].execution.input.push_back(nat.0);
-                network[0].execution.input.push_back(nat.1);
-                last_nat_y = nat.1;
+                network
+                    .machine_mut(0)
+                    .expect("The NAT sends to computer 0")
+                    .execution
+                    .input
+                    .extend(&nat);
+                last_nat_y = nat[1];
             }
         }
-
-        Some(final_y.to_string())
     }
 
     fn problem_number() -> usize {
@@ -1,13 +1,13 @@
-use advent_of_code_2019::cpu::{parse_program, Execution, IntCode, Memory};
+use advent_of_code_2019::cpu::ascii::AsciiConsole;
+use advent_of_code_2019::cpu::{parse_program, Execution, Memory};
 use advent_of_code_2019::problem::{run, Problem, ProblemState};
 use env_logger::Env;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{HashSet, VecDeque};
+use std::collections::HashSet;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::mem;
 
 static DANGEROUS_ITEMS: [&str; 5] = [
     "infinite loop",
@@ -65,7 +65,7 @@ impl Problem for TwentyFive {
 }
 
 struct Explorer {
-    execution: Execution,
+    console: AsciiConsole,
     last_room: String,
     path: Vec<Direction>,
     moved: bool,
@@ -74,7 +74,7 @@ struct Explorer {
 impl Explorer {
     fn new(program: &<TwentyFive as Problem>::Input) -> Explorer {
         Explorer {
-            execution: Execution::new(program.clone()),
+            console: AsciiConsole::new(Execution::new(program.clone())),
             last_room: String::new(),
             path: vec![],
             moved: false,
@@ -237,16 +237,16 @@ impl Explorer {
 
     fn send_command(&mut self, maybe_command: Option<&str>) -> Response {
         if let Some(command) = maybe_command {
-            let mut raw_command: VecDeque<IntCode> =
-                command.chars().map(|c| c as IntCode).collect();
-            raw_command.push_back('\n' as IntCode);
-
-            self.execution.input.append(&mut raw_command);
+            self.console.send_line(command);
         }
 
-        self.execution.run().ok();
+        let output = self
+            .console
+            .read_until_prompt("Command?\n")
+            .map(|response| response.text)
+            .unwrap_or_default();
 
-        let response = Response::parse(&mut self.execution);
+        let response = Response::parse(output);
 
         if let Some(command) = maybe_command {
             self.check_movement(command, &response);
@@ -326,18 +326,13 @@ impl Display for Response {
 }
 
 impl Response {
-    fn parse(execution: &mut Execution) -> Response {
+    fn parse(output: String) -> Response {
         lazy_static! {
             static ref MOVED_RE: Regex = Regex::new(r"== ([^=]+) ==\n(.+)\n\nDoors here lead:\n((?:- \w+\n)+)(?:\nItems here:\n((?:- .+\n)+))?\nCommand\?").unwrap();
             static ref INVENTORY_RE: Regex = Regex::new(r"Items in your inventory:\n((?:- .+\n)+)\nCommand\?").unwrap();
             static ref LIST_RE: Regex = Regex::new(r"- (.+)").unwrap();
         }
 
-        let output = mem::replace(&mut execution.output, VecDeque::new())
-            .iter()
-            .map(|&i| i as u8 as char)
-            .collect::<String>();
-
         if let Some(parsed) = MOVED_RE.captures(&output) {
             let directions = parsed[3]
                 .split('\n')
@@ -1,8 +1,8 @@
-use advent_of_code_2019::cpu::{parse_program, Execution, ExecutionState, Memory};
+use advent_of_code_2019::cpu::pipeline::{search_phases, Pipeline};
+use advent_of_code_2019::cpu::{parse_program, Memory};
 use advent_of_code_2019::example;
 use advent_of_code_2019::problem::{run, Problem, ProblemState, RunFor};
 use env_logger::Env;
-use permutohedron::LexicalPermutation;
 
 struct Seven {}
 
@@ -15,60 +15,19 @@ impl Problem for Seven {
     }
 
     fn part_1(program: &Self::Input, _state: &ProblemState<Self::Extra>) -> Option<String> {
-        let mut max_thrust = 0;
-        let mut phase_settings = [0, 1, 2, 3, 4];
-        loop {
-            let mut output = 0;
-            for phase in phase_settings.iter() {
-                let mut execution: Execution =
-                    Execution::new_input(program.clone(), vec![*phase, output]);
-
-                execution.run().expect("This should always work");
-
-                output = execution.output.pop_front().unwrap()
-            }
-
-            max_thrust = max_thrust.max(output);
-
-            if !phase_settings.next_permutation() {
-                break;
-            }
-        }
+        let (_, max_thrust) = search_phases(&[0, 1, 2, 3, 4], |phases| {
+            Pipeline::chain(program, phases, 0)
+        })
+        .expect("This should always work")?;
 
         Some(format!("{}", max_thrust))
     }
 
     fn part_2(program: &Self::Input, _state: &ProblemState<Self::Extra>) -> Option<String> {
-        let mut max_thrust = 0;
-        let mut phase_settings = [5, 6, 7, 8, 9];
-        loop {
-            let mut executions =
-                vec![Execution::new_input(program.clone(), vec![]); phase_settings.len()];
-
-            for (i, phase) in phase_settings.iter().enumerate() {
-                executions[i].input.push_back(*phase);
-            }
-
-            let mut output = 0;
-            'feedback: loop {
-                let mut state = ExecutionState::Running;
-                for execution in executions.iter_mut() {
-                    execution.input.push_back(output);
-                    state = execution.run().expect("This should always work");
-                    output = execution.output.pop_front().unwrap();
-                }
-
-                if ExecutionState::Halted == state {
-                    break 'feedback;
-                }
-            }
-
-            max_thrust = max_thrust.max(output);
-
-            if !phase_settings.next_permutation() {
-                break;
-            }
-        }
+        let (_, max_thrust) = search_phases(&[5, 6, 7, 8, 9], |phases| {
+            Pipeline::ring(program, phases, 0)
+        })
+        .expect("This should always work")?;
 
         Some(format!("{}", max_thrust))
     }
@@ -0,0 +1,191 @@
+use advent_of_code_2019::cpu::debugger::{Debugger, Stop, Watch};
+use advent_of_code_2019::cpu::disassembler::disassemble_with;
+use advent_of_code_2019::cpu::{parse_program, CPUError, Execution, IntCode};
+use std::io::{BufRead, Write};
+use std::{env, fs, io};
+
+static HELP: &str = r#"commands:
+  s, step [n]            execute n instructions, ignoring breaks
+  c, continue            run until a break, halt or input is needed
+  b, break <ip>          add a breakpoint
+  d, delete <ip>         remove a breakpoint
+  w, watch <addr> [rw]   watch reads (r), writes (w) or both (rw, the default)
+  u, unwatch <addr>      remove a watchpoint
+  bi, bo                 toggle breaking on input / output instructions
+  i, info                list breakpoints and watchpoints
+  r, regs                show ip and relative_base
+  x <addr> [n]           show n memory cells
+  l, list [n]            disassemble n instructions at ip
+  in <values...>         queue numeric input
+  ascii <text>           queue a line of ascii input
+  o, out                 print and clear the output queue
+  q, quit"#;
+
+fn main() {
+    let path = env::args().nth(1).expect("Usage: debugger <program file>");
+    let raw_program =
+        fs::read_to_string(&path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e));
+
+    let mut debugger = Debugger::new(Execution::new(parse_program(&raw_program)));
+
+    println!("{}", HELP);
+    print_registers(&debugger);
+
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().expect("Couldn't flush stdout");
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).expect("Couldn't read stdin") == 0 {
+            break;
+        }
+
+        let mut args = line.split_whitespace();
+        let command = match args.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let args: Vec<&str> = args.collect();
+
+        match command {
+            "s" | "step" => {
+                for _ in 0..parse_arg(&args, 0).unwrap_or(1) {
+                    let stop = report(debugger.step());
+                    if stop != Some(Stop::Stepped) {
+                        break;
+                    }
+                }
+                print_listing(&debugger, 1);
+            }
+            "c" | "continue" => {
+                report(debugger.resume());
+                print_listing(&debugger, 1);
+            }
+            "b" | "break" => match parse_arg(&args, 0) {
+                Some(ip) => debugger.add_breakpoint(ip),
+                None => println!("break <ip>"),
+            },
+            "d" | "delete" => match parse_arg(&args, 0) {
+                Some(ip) if debugger.remove_breakpoint(ip) => (),
+                _ => println!("no such breakpoint"),
+            },
+            "w" | "watch" => {
+                let watch = match args.get(1).cloned() {
+                    Some("r") => Some(Watch::Read),
+                    Some("w") => Some(Watch::Write),
+                    Some("rw") | None => Some(Watch::ReadWrite),
+                    _ => None,
+                };
+
+                match (parse_arg(&args, 0), watch) {
+                    (Some(address), Some(watch)) => debugger.add_watchpoint(address, watch),
+                    _ => println!("watch <addr> [r|w|rw]"),
+                }
+            }
+            "u" | "unwatch" => match parse_arg(&args, 0) {
+                Some(address) if debugger.remove_watchpoint(address) => (),
+                _ => println!("no such watchpoint"),
+            },
+            "bi" => {
+                debugger.break_on_input = !debugger.break_on_input;
+                println!("break on input: {}", debugger.break_on_input);
+            }
+            "bo" => {
+                debugger.break_on_output = !debugger.break_on_output;
+                println!("break on output: {}", debugger.break_on_output);
+            }
+            "i" | "info" => {
+                println!("breakpoints: {:?}", debugger.breakpoints());
+                println!("watchpoints: {:?}", debugger.watchpoints());
+                println!(
+                    "break on input: {}, break on output: {}",
+                    debugger.break_on_input, debugger.break_on_output
+                );
+            }
+            "r" | "regs" => print_registers(&debugger),
+            "x" => match parse_arg::<usize>(&args, 0) {
+                Some(address) => {
+                    let count = parse_arg(&args, 1).unwrap_or(1);
+                    match address.checked_add(count) {
+                        Some(end) => {
+                            for a in address..end {
+                                println!("{:>5}: {}", a, debugger.execution.peek(a));
+                            }
+                        }
+                        None => println!(
+                            "{} words from {} runs past the end of memory",
+                            count, address
+                        ),
+                    }
+                }
+                None => println!("x <addr> [n]"),
+            },
+            "l" | "list" => print_listing(&debugger, parse_arg(&args, 0).unwrap_or(10)),
+            "in" => {
+                match args
+                    .iter()
+                    .map(|a| a.parse::<IntCode>())
+                    .collect::<Result<Vec<_>, _>>()
+                {
+                    Ok(values) => debugger.execution.input.extend(values),
+                    Err(e) => println!("bad input: {}", e),
+                }
+            }
+            "ascii" => {
+                let text = args.join(" ");
+                debugger
+                    .execution
+                    .input
+                    .extend(text.chars().chain(Some('\n')).map(|c| c as IntCode));
+            }
+            "o" | "out" => {
+                let output: Vec<IntCode> = debugger.execution.output.drain(..).collect();
+                println!("{:?}", output);
+            }
+            "q" | "quit" => break,
+            _ => println!("{}", HELP),
+        }
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str], index: usize) -> Option<T> {
+    args.get(index).and_then(|arg| arg.parse().ok())
+}
+
+fn report(result: Result<Stop, CPUError>) -> Option<Stop> {
+    match result {
+        Ok(Stop::Stepped) => Some(Stop::Stepped),
+        Ok(stop) => {
+            println!("stopped: {:?}", stop);
+            Some(stop)
+        }
+        Err(e) => {
+            println!("CPU Error: {:?}", e);
+            None
+        }
+    }
+}
+
+fn print_registers(debugger: &Debugger) {
+    println!(
+        "ip: {} relative_base: {}",
+        debugger.execution.ip, debugger.execution.relative_base
+    );
+}
+
+fn print_listing(debugger: &Debugger, count: usize) {
+    let ip = debugger.execution.ip;
+    let memory = &debugger.execution.memory;
+    // instructions are at most 4 words
+    let end = memory.len().min(ip.saturating_add(count.saturating_mul(4)));
+
+    for mut line in disassemble_with(&memory[ip.min(end)..end], &debugger.execution.extensions)
+        .into_iter()
+        .take(count)
+    {
+        line.address += ip;
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,33 @@
+use advent_of_code_2019::cpu::cfg::ControlFlowGraph;
+use advent_of_code_2019::cpu::disassembler::listing;
+use advent_of_code_2019::cpu::parse_program;
+use std::io::Read;
+use std::{env, fs, io};
+
+/// Prints the listing for a program file, or stdin when no file (or `-`) is given. With `--dot`
+/// prints its control flow graph in Graphviz DOT format instead
+fn main() {
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg == "--dot");
+
+    let raw_program = match paths.into_iter().next() {
+        Some(ref path) if path != "-" => {
+            fs::read_to_string(path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e))
+        }
+        _ => {
+            let mut raw_program = String::new();
+            io::stdin()
+                .read_to_string(&mut raw_program)
+                .expect("Couldn't read stdin");
+
+            raw_program
+        }
+    };
+
+    let memory = parse_program(&raw_program);
+    if flags.is_empty() {
+        println!("{}", listing(&memory));
+    } else {
+        print!("{}", ControlFlowGraph::new(&memory).to_dot());
+    }
+}
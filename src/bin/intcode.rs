@@ -0,0 +1,268 @@
+use advent_of_code_2019::cpu::bignum::{parse_big_program, BigExecution, BigMemory};
+use advent_of_code_2019::cpu::io::{AsciiStdin, AsciiStdout, Input, Output};
+use advent_of_code_2019::cpu::{
+    try_parse_program, Arithmetic, Execution, ExecutionState, IntCode, Memory,
+};
+use num::bigint::BigInt;
+use num::ToPrimitive;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::{env, fs, io, process};
+
+static USAGE: &str = r#"Usage: intcode [options] [program file | -]
+Runs an intcode program from a file, or stdin when no file (or `-`) is given
+options:
+  -i, --input <values>      queue comma separated numeric input
+  -l, --line <text>         queue a line of ascii input
+  -B, --bignum              run with arbitrary precision words, only with --input, --line, --set and --memory
+  -a, --ascii               print output as ascii and read more input from stdin
+  -s, --set <addr>=<value>  patch memory before running, e.g. --set 1=12 --set 2=2
+  -b, --budget <n>          stop after executing n instructions
+  -o, --overflow <mode>     wrapping (the default), checked or saturating arithmetic
+  -m, --memory <addr>       print a memory cell once we stop instead of the output
+  -p, --profile             print a profile of what executed to stderr
+      --profile-json        print the profile to stderr as json
+  -c, --coverage            print a listing marking code that never executed to stderr"#;
+
+#[derive(Debug, Default)]
+struct Options {
+    path: Option<String>,
+    /// values are only checked against `IntCode` once we know we aren't running with --bignum
+    input: VecDeque<BigInt>,
+    ascii: bool,
+    patches: Vec<(usize, BigInt)>,
+    budget: Option<usize>,
+    arithmetic: Option<Arithmetic>,
+    cells: Vec<usize>,
+    profile: Option<ProfileFormat>,
+    coverage: bool,
+    bignum: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProfileFormat {
+    Table,
+    Json,
+}
+
+fn main() {
+    let options = parse_options(env::args().skip(1));
+
+    let raw_program = match options.path {
+        Some(ref path) if path != "-" => fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(&format!("Couldn't read {}: {}", path, e))),
+        _ => {
+            let mut raw_program = String::new();
+            io::stdin()
+                .read_to_string(&mut raw_program)
+                .unwrap_or_else(|e| fail(&format!("Couldn't read stdin: {}", e)));
+
+            raw_program
+        }
+    };
+    if options.bignum {
+        let memory = parse_big_program(&raw_program).unwrap_or_else(|e| fail(&e.to_string()));
+        run_big_program(memory, &options);
+        return;
+    }
+    let memory: Memory = try_parse_program(&raw_program).unwrap_or_else(|e| fail(&e.to_string()));
+    let input: VecDeque<IntCode> = options.input.iter().map(small).collect();
+
+    if options.ascii {
+        let input = AsciiStdin::with_queued(input);
+        let mut execution = Execution::with_io(memory, input, AsciiStdout);
+        run_program(&mut execution, &options);
+    } else {
+        let mut execution = Execution::with_io(memory, input, VecDeque::new());
+        run_program(&mut execution, &options);
+
+        if options.cells.is_empty() {
+            for output in execution.output.iter() {
+                println!("{}", output);
+            }
+        }
+    }
+}
+
+fn run_program<I: Input, O: Output>(execution: &mut Execution<I, O>, options: &Options) {
+    for (address, value) in options.patches.iter() {
+        execution[*address] = small(value);
+    }
+    execution.arithmetic = options.arithmetic.unwrap_or_default();
+    if options.profile.is_some() {
+        execution.start_profile();
+    }
+    if options.coverage {
+        execution.start_coverage();
+    }
+
+    let result = match options.budget {
+        Some(budget) => execution.run_for(budget),
+        None => execution.run(),
+    };
+
+    if let Some(profile) = execution.take_profile() {
+        match options.profile {
+            Some(ProfileFormat::Json) => eprintln!("{}", profile.to_json()),
+            _ => eprint!("{}", profile),
+        }
+    }
+
+    if let Some(coverage) = execution.take_coverage() {
+        eprintln!("{}", coverage.listing(&execution.memory));
+    }
+
+    match result {
+        Ok(ExecutionState::Halted) => (),
+        Ok(ExecutionState::NeedsInput) => {
+            eprintln!("stopped at ip {} waiting for input", execution.ip)
+        }
+        Ok(ExecutionState::BudgetExhausted) => eprintln!(
+            "stopped at ip {} after our budget of {} instructions",
+            execution.ip,
+            options.budget.unwrap_or_default()
+        ),
+        Ok(ExecutionState::Running) => unreachable!(),
+        Err(e) => {
+            eprintln!("CPU Error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    for &address in options.cells.iter() {
+        println!("{}: {}", address, execution.peek(address));
+    }
+}
+
+fn run_big_program(memory: BigMemory, options: &Options) {
+    let input = options.input.iter().cloned().collect();
+    let mut execution = BigExecution::new_input(memory, input);
+    for (address, value) in options.patches.iter() {
+        execution.set(*address, value.clone());
+    }
+
+    match execution.run() {
+        Ok(ExecutionState::NeedsInput) => {
+            eprintln!("stopped at ip {} waiting for input", execution.ip)
+        }
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("CPU Error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if options.cells.is_empty() {
+        for output in execution.output.iter() {
+            println!("{}", output);
+        }
+    }
+    for &address in options.cells.iter() {
+        println!("{}: {}", address, execution.get(address));
+    }
+}
+
+fn parse_options<A: Iterator<Item = String>>(mut args: A) -> Options {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .unwrap_or_else(|| fail(&format!("{} needs a value", name)))
+        };
+
+        match arg.as_str() {
+            "-i" | "--input" => {
+                let values = parse_big_program(&value(&arg))
+                    .unwrap_or_else(|e| fail(&format!("bad input: {}", e)));
+                options.input.extend(values);
+            }
+            "-l" | "--line" => {
+                let line = value(&arg);
+                options.input.extend(
+                    line.chars()
+                        .chain(Some('\n'))
+                        .map(|c| BigInt::from(c as u32)),
+                );
+            }
+            "-a" | "--ascii" => options.ascii = true,
+            "-B" | "--bignum" => options.bignum = true,
+            "-s" | "--set" => {
+                let patch = value(&arg);
+                let mut parts = patch.splitn(2, '=');
+                match (
+                    parts.next().and_then(|a| a.trim().parse().ok()),
+                    parts.next().and_then(|v| v.trim().parse().ok()),
+                ) {
+                    (Some(address), Some(value)) => options.patches.push((address, value)),
+                    _ => fail(&format!("bad patch {:?}, expected <addr>=<value>", patch)),
+                }
+            }
+            "-b" | "--budget" => {
+                let budget = value(&arg);
+                options.budget = Some(
+                    budget
+                        .parse()
+                        .unwrap_or_else(|_| fail(&format!("bad budget {:?}", budget))),
+                );
+            }
+            "-o" | "--overflow" => {
+                options.arithmetic = Some(match value(&arg).as_str() {
+                    "wrapping" => Arithmetic::Wrapping,
+                    "checked" => Arithmetic::Checked,
+                    "saturating" => Arithmetic::Saturating,
+                    mode => fail(&format!("bad overflow mode {:?}", mode)),
+                })
+            }
+            "-m" | "--memory" => {
+                let address = value(&arg);
+                options.cells.push(
+                    address
+                        .parse()
+                        .unwrap_or_else(|_| fail(&format!("bad address {:?}", address))),
+                );
+            }
+            "-p" | "--profile" => options.profile = Some(ProfileFormat::Table),
+            "--profile-json" => options.profile = Some(ProfileFormat::Json),
+            "-c" | "--coverage" => options.coverage = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if options.path.is_none() && (arg == "-" || !arg.starts_with('-')) => {
+                options.path = Some(arg)
+            }
+            _ => fail(&format!("unexpected argument {:?}", arg)),
+        }
+    }
+
+    if options.bignum {
+        let unsupported = [
+            ("--ascii", options.ascii),
+            ("--budget", options.budget.is_some()),
+            ("--overflow", options.arithmetic.is_some()),
+            ("--profile", options.profile == Some(ProfileFormat::Table)),
+            (
+                "--profile-json",
+                options.profile == Some(ProfileFormat::Json),
+            ),
+            ("--coverage", options.coverage),
+        ];
+        if let Some((flag, _)) = unsupported.iter().find(|(_, used)| *used) {
+            fail(&format!("{} can't be used with --bignum", flag));
+        }
+    }
+
+    options
+}
+
+/// `value` as an `IntCode`, failing if it needs --bignum
+fn small(value: &BigInt) -> IntCode {
+    value
+        .to_i64()
+        .unwrap_or_else(|| fail(&format!("{} doesn't fit without --bignum", value)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}
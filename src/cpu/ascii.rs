@@ -0,0 +1,155 @@
+use crate::cpu::{Execution, ExecutionState, IntCode, Result};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// Everything an execution printed since we last read from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub text: String,
+    /// outputs that aren't ascii, usually the answer we're after
+    pub values: Vec<IntCode>,
+    pub state: ExecutionState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptEntry {
+    Sent(String),
+    Received(String),
+    Value(IntCode),
+}
+
+impl Display for TranscriptEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptEntry::Sent(line) => writeln!(f, "{}", line),
+            TranscriptEntry::Received(text) => write!(f, "{}", text),
+            TranscriptEntry::Value(value) => writeln!(f, "{}", value),
+        }
+    }
+}
+
+/// Talks to ascii programs a line at a time, keeping a transcript of the whole conversation
+#[derive(Debug, Clone)]
+pub struct AsciiConsole {
+    pub execution: Execution,
+    transcript: Vec<TranscriptEntry>,
+}
+
+impl AsciiConsole {
+    pub fn new(execution: Execution) -> AsciiConsole {
+        AsciiConsole {
+            execution,
+            transcript: vec![],
+        }
+    }
+
+    /// Queues a line of input, adding the trailing newline
+    pub fn send_line(&mut self, line: &str) {
+        self.execution
+            .input
+            .extend(line.chars().chain(Some('\n')).map(|c| c as IntCode));
+        self.transcript
+            .push(TranscriptEntry::Sent(line.to_string()));
+    }
+
+    /// Runs until the program halts or wants more input
+    pub fn read(&mut self) -> Result<Response> {
+        let state = self.execution.run()?;
+
+        Ok(self.receive(state))
+    }
+
+    /// Runs until the program prints `prompt`, halts or wants more input
+    pub fn read_until_prompt(&mut self, prompt: &str) -> Result<Response> {
+        let mut text = String::new();
+        let mut state = ExecutionState::Running;
+        while state == ExecutionState::Running && !text.ends_with(prompt) {
+            let printed = self.execution.output.len();
+            state = self.execution.step()?;
+            if let Some(&c) = self.execution.output.get(printed) {
+                if is_ascii(c) {
+                    text.push(c as u8 as char);
+                }
+            }
+        }
+
+        Ok(self.receive(state))
+    }
+
+    pub fn transcript(&self) -> &[TranscriptEntry] {
+        &self.transcript
+    }
+
+    fn receive(&mut self, state: ExecutionState) -> Response {
+        let mut response = Response {
+            text: String::new(),
+            values: vec![],
+            state,
+        };
+
+        for value in self.execution.output.drain(..) {
+            if is_ascii(value) {
+                response.text.push(value as u8 as char);
+            } else {
+                response.values.push(value);
+            }
+        }
+
+        if !response.text.is_empty() {
+            self.transcript
+                .push(TranscriptEntry::Received(response.text.clone()));
+        }
+        self.transcript
+            .extend(response.values.iter().cloned().map(TranscriptEntry::Value));
+
+        response
+    }
+}
+
+fn is_ascii(value: IntCode) -> bool {
+    (0..128).contains(&value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+
+    #[test]
+    fn conversation() {
+        // greet, then echo a line back followed by its length
+        let program = assemble(
+            r#"
+                    OUT #62
+                    OUT #10
+            read:   IN [char]
+                    EQ [char], #10, [done]
+                    JT [done], #finish
+                    OUT [char]
+                    ADD [count], #1, [count]
+                    JT #1, #read
+            finish: OUT #10
+                    OUT [count]
+                    HLT
+            char:   DATA 0
+            done:   DATA 0
+            count:  DATA 1000
+            "#,
+        )
+        .unwrap();
+        let mut console = AsciiConsole::new(Execution::new(program));
+
+        let greeting = console.read_until_prompt(">\n").unwrap();
+        assert_eq!(greeting.text, ">\n");
+        assert_eq!(greeting.state, ExecutionState::Running);
+
+        console.send_line("hi");
+        let response = console.read().unwrap();
+        assert_eq!(response.text, "hi\n");
+        assert_eq!(response.values, vec![1002]);
+        assert_eq!(response.state, ExecutionState::Halted);
+
+        let transcript: String = console.transcript().iter().map(|e| e.to_string()).collect();
+        assert_eq!(transcript, ">\nhi\nhi\n1002\n");
+    }
+}
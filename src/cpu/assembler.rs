@@ -0,0 +1,437 @@
+use crate::cpu::extensions::{Extension, Extensions};
+use crate::cpu::{Instruction, IntCode, Memory, Mode, OpCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for AssemblyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+type Result<T> = std::result::Result<T, AssemblyError>;
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(IntCode),
+    Label(String, usize, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Instruction(OpCode, Vec<(Mode, Value)>),
+    Data(Vec<Value>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+/// Assembles source like:
+/// ```text
+/// ; echo input until we read a 0
+/// loop:   IN [value]
+///         JF [value], #end
+///         OUT [value]
+///         JT #1, #loop
+/// end:    HLT
+/// value:  DATA 0
+/// ```
+/// Operands are `[addr]`, `#imm` or `rb+off` and values can be numbers or labels. A numeric label
+/// like `12:` asserts the current address, so disassembler listings assemble back to their memory.
+pub fn assemble(source: &str) -> Result<Memory> {
+    assemble_with(source, &Extensions::new())
+}
+
+/// Assembles `source`, also accepting the mnemonics of `extensions`
+pub fn assemble_with(source: &str, extensions: &Extensions) -> Result<Memory> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+
+    let mut address = 0;
+    for (i, raw_line) in source.lines().enumerate() {
+        let mut cursor = Cursor::new(raw_line, i + 1);
+
+        while let Some((column, name)) = cursor.label() {
+            match name.parse::<usize>() {
+                Ok(expected) if expected != address => {
+                    return Err(cursor.error_at(
+                        column,
+                        format!("expected address {} but we're at {}", expected, address),
+                    ));
+                }
+                Ok(_) => (),
+                // operands starting with a digit are numbers, so this could never be referenced
+                Err(_) if name.starts_with(|c: char| c.is_ascii_digit()) => {
+                    return Err(
+                        cursor.error_at(column, format!("label {} can't start with a digit", name))
+                    );
+                }
+                Err(_) => {
+                    if labels.insert(name.clone(), address).is_some() {
+                        return Err(cursor.error_at(column, format!("duplicate label {}", name)));
+                    }
+                }
+            }
+        }
+
+        if let Some(statement) = cursor.statement(extensions)? {
+            address += statement.size();
+            statements.push(statement);
+        }
+    }
+
+    let resolve = |value: &Value| -> Result<IntCode> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            Value::Label(name, line, column) => labels
+                .get(name)
+                .map(|&address| address as IntCode)
+                .ok_or_else(|| AssemblyError {
+                    line: *line,
+                    column: *column,
+                    message: format!("undefined label {}", name),
+                }),
+        }
+    };
+
+    let mut memory = Vec::with_capacity(address);
+    for statement in statements.iter() {
+        match statement {
+            Statement::Instruction(op_code, operands) => {
+                let mut modes = [Mode::Position, Mode::Position, Mode::Position];
+                for (i, (mode, _)) in operands.iter().enumerate() {
+                    modes[i] = *mode;
+                }
+
+                let instruction = Instruction {
+                    op_code: *op_code,
+                    modes,
+                };
+                memory.push(instruction.encode());
+
+                for (_, value) in operands.iter() {
+                    memory.push(resolve(value)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values.iter() {
+                    memory.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn new(raw_line: &str, line: usize) -> Cursor {
+        Cursor {
+            chars: raw_line.chars().take_while(|&c| c != ';').collect(),
+            position: 0,
+            line,
+        }
+    }
+
+    fn label(&mut self) -> Option<(usize, String)> {
+        let start = self.position;
+        self.skip_whitespace();
+
+        let column = self.column();
+        let word = self.word();
+        self.skip_whitespace();
+
+        if !word.is_empty() && !word.starts_with('-') && self.peek() == Some(':') {
+            self.position += 1;
+            Some((column, word))
+        } else {
+            self.position = start;
+            None
+        }
+    }
+
+    fn statement(&mut self, extensions: &Extensions) -> Result<Option<Statement>> {
+        self.skip_whitespace();
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+
+        let column = self.column();
+        let mnemonic = self.word().to_uppercase();
+
+        let statement = if mnemonic == "DATA" {
+            let mut values = vec![self.value()?];
+            while self.comma() {
+                values.push(self.value()?);
+            }
+
+            Statement::Data(values)
+        } else {
+            let op_code = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99]
+                .iter()
+                .filter_map(|&code| OpCode::new(code).ok())
+                .chain(extensions.registered().into_iter().map(Extension::op_code))
+                .find(|op_code| op_code.mnemonic() == mnemonic)
+                .ok_or_else(|| self.error_at(column, format!("unknown mnemonic {}", mnemonic)))?;
+
+            let mut operands = vec![];
+            if op_code.parameter_count() > 0 {
+                operands.push(self.operand()?);
+                while self.comma() {
+                    operands.push(self.operand()?);
+                }
+            }
+
+            if operands.len() != op_code.parameter_count() {
+                return Err(self.error_at(
+                    column,
+                    format!(
+                        "{} takes {} operands but found {}",
+                        mnemonic,
+                        op_code.parameter_count(),
+                        operands.len()
+                    ),
+                ));
+            }
+
+            Statement::Instruction(op_code, operands)
+        };
+
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(Some(statement)),
+            Some(c) => Err(self.error(format!("unexpected {:?}", c))),
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Mode, Value)> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('[') => {
+                self.position += 1;
+                let value = self.value()?;
+
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    Ok((Mode::Position, value))
+                } else {
+                    Err(self.error("expected ]".to_string()))
+                }
+            }
+            Some('#') => {
+                self.position += 1;
+                Ok((Mode::Immediate, self.value()?))
+            }
+            Some('r') | Some('R') => {
+                let column = self.column();
+                if self.word().to_lowercase() != "rb" {
+                    return Err(self.error_at(column, "expected an operand".to_string()));
+                }
+
+                self.skip_whitespace();
+                let offset = match self.peek() {
+                    Some('+') => {
+                        self.position += 1;
+                        self.value()?
+                    }
+                    Some('-') => self.value()?,
+                    _ => Value::Number(0),
+                };
+
+                Ok((Mode::Relative, offset))
+            }
+            _ => Err(self.error("expected an operand".to_string())),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+
+        let column = self.column();
+        let word = self.word();
+        match word.chars().next() {
+            Some(c) if c == '-' || c.is_ascii_digit() => word
+                .parse::<IntCode>()
+                .map(Value::Number)
+                .map_err(|_| self.error_at(column, format!("invalid number {}", word))),
+            Some(c) if c.is_alphabetic() || c == '_' => Ok(Value::Label(word, self.line, column)),
+            _ => Err(self.error_at(column, "expected a number or label".to_string())),
+        }
+    }
+
+    fn comma(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(',') {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            let sign = c == '-' && self.position == start;
+            if c.is_alphanumeric() || c == '_' || sign {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn column(&self) -> usize {
+        self.position + 1
+    }
+
+    fn error(&self, message: String) -> AssemblyError {
+        self.error_at(self.column(), message)
+    }
+
+    fn error_at(&self, column: usize, message: String) -> AssemblyError {
+        AssemblyError {
+            line: self.line,
+            column,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::disassembler::{listing, listing_with};
+    use crate::cpu::extensions::Role;
+    use crate::cpu::{parse_program, Execution};
+
+    #[test]
+    fn assemble_and_run() {
+        let memory = assemble(
+            r#"
+            ; echo input until we read a 0
+            loop:   IN [value]
+                    jf [value], #end
+                    OUT [value]
+                    JT #1, #loop
+            end:    HLT
+            value:  DATA 0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(memory, vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+
+        let mut execution = Execution::new_input(memory, vec![3, 7, 0]);
+        execution.run().unwrap();
+        assert_eq!(Vec::from(execution.output), vec![3, 7]);
+
+        assert_eq!(
+            assemble("ARB #5\nOUT rb-2\nOUT rb\nOUT rb+ 1\nHLT").unwrap(),
+            vec![109, 5, 204, -2, 204, 0, 204, 1, 99]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for raw_program in [
+            include_str!("../bin/9_input.txt"),
+            include_str!("../bin/21_input.txt"),
+            include_str!("../bin/25_input.txt"),
+        ]
+        .iter()
+        {
+            let program = parse_program(raw_program);
+            assert_eq!(assemble(&listing(&program)), Ok(program));
+        }
+    }
+
+    #[test]
+    fn extended_round_trip() {
+        let mut extensions = Extensions::new();
+        extensions.register(
+            10,
+            "DIV",
+            &[Role::Read, Role::Read, Role::Write],
+            |_| Ok(()),
+        );
+
+        let source = "    0: DIV [5], #7, rb+1\n    4: HLT\n    5: DATA 42";
+        let program = assemble_with(source, &extensions).unwrap();
+        assert_eq!(program, vec![21010, 5, 7, 1, 99, 42]);
+        assert_eq!(listing_with(&program, &extensions), source);
+
+        // without the extension it's just data
+        assert!(listing(&program).starts_with("    0: DATA 21010"));
+        assert!(assemble(source).is_err());
+    }
+
+    #[test]
+    fn assembly_errors() {
+        let error = |line, column, message: &str| {
+            Err(AssemblyError {
+                line,
+                column,
+                message: message.to_string(),
+            })
+        };
+
+        assert_eq!(
+            assemble("ADD #1, #2, [0]\n  FOO [1]"),
+            error(2, 3, "unknown mnemonic FOO")
+        );
+        assert_eq!(
+            assemble("ADD #1, #2"),
+            error(1, 1, "ADD takes 3 operands but found 2")
+        );
+        assert_eq!(assemble("OUT [1"), error(1, 7, "expected ]"));
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            error(1, 9, "undefined label nowhere")
+        );
+        assert_eq!(
+            assemble("HLT\n2: HLT"),
+            error(2, 1, "expected address 2 but we're at 1")
+        );
+        assert_eq!(
+            assemble("HLT\n  1abc: JT #1, #1abc"),
+            error(2, 3, "label 1abc can't start with a digit")
+        );
+    }
+}
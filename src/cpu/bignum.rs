@@ -0,0 +1,232 @@
+use crate::cpu::interpreter::{Machine, Word};
+use crate::cpu::{
+    interpreter, tokens, Arithmetic, CPUError, ExecutionState, Instruction, IntCode, Memory,
+    ParseError, ParseOptions, Result, CONTIGUOUS_LIMIT,
+};
+use num::bigint::BigInt;
+use num::{Signed, ToPrimitive, Zero};
+use std::collections::{HashMap, VecDeque};
+use std::result;
+
+pub type BigMemory = Vec<BigInt>;
+
+/// Runs programs with arbitrary precision words, for when values outgrow an `IntCode`. This is a
+/// lot slower than `Execution`, and only has the basics: queues for io and no tracing, profiling
+/// or caching
+#[derive(Debug, Clone, PartialEq)]
+pub struct BigExecution {
+    pub ip: usize,
+    pub relative_base: usize,
+    pub memory: BigMemory,
+    /// everything written past `CONTIGUOUS_LIMIT`
+    pub sparse: HashMap<usize, BigInt>,
+    pub input: VecDeque<BigInt>,
+    pub output: VecDeque<BigInt>,
+}
+
+impl BigExecution {
+    pub fn new(memory: BigMemory) -> BigExecution {
+        Self::new_input(memory, vec![])
+    }
+
+    pub fn new_input(memory: BigMemory, input: Vec<BigInt>) -> BigExecution {
+        BigExecution {
+            ip: 0,
+            relative_base: 0,
+            memory,
+            sparse: HashMap::new(),
+            input: input.into(),
+            output: VecDeque::new(),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<ExecutionState> {
+        let mut state = ExecutionState::Running;
+        while state == ExecutionState::Running {
+            state = self.step()?;
+        }
+
+        Ok(state)
+    }
+
+    pub fn step(&mut self) -> Result<ExecutionState> {
+        interpreter::step(self)
+    }
+
+    /// Reads a cell, anything we've never written is 0
+    pub fn get(&self, address: usize) -> BigInt {
+        self.memory
+            .get(address)
+            .or_else(|| self.sparse.get(&address))
+            .cloned()
+            .unwrap_or_else(BigInt::zero)
+    }
+
+    pub fn set(&mut self, address: usize, value: BigInt) {
+        if address < self.memory.len() {
+            self.memory[address] = value;
+        } else if address < CONTIGUOUS_LIMIT {
+            self.memory.resize(address + 1, BigInt::zero());
+            self.memory[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+}
+
+impl Machine for BigExecution {
+    type Word = BigInt;
+
+    fn ip(&self) -> usize {
+        self.ip
+    }
+
+    fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+    }
+
+    fn relative_base(&self) -> usize {
+        self.relative_base
+    }
+
+    fn set_relative_base(&mut self, relative_base: usize) {
+        self.relative_base = relative_base;
+    }
+
+    fn decode(&mut self) -> Result<Instruction> {
+        let word = self.get(self.ip);
+        match word.to_i64() {
+            Some(word) => Instruction::new(word).map_err(|e| e.at(self.ip)),
+            // far too big to be an op code
+            None => Err(CPUError::InvalidOpCode {
+                ip: self.ip,
+                word: word.saturate(),
+            }),
+        }
+    }
+
+    fn fetch(&self, address: usize) -> BigInt {
+        self.get(address)
+    }
+
+    fn load(&self, address: usize) -> BigInt {
+        self.get(address)
+    }
+
+    fn store(&mut self, address: usize, value: BigInt) -> Result<()> {
+        self.set(address, value);
+        Ok(())
+    }
+
+    fn read_input(&mut self) -> Result<Option<BigInt>> {
+        Ok(self.input.pop_front())
+    }
+
+    fn write_output(&mut self, value: BigInt) {
+        self.output.push_back(value);
+    }
+}
+
+/// Nothing overflows, so the arithmetic policy doesn't matter
+impl Word for BigInt {
+    fn from_int(value: IntCode) -> Self {
+        BigInt::from(value)
+    }
+
+    fn saturate(&self) -> IntCode {
+        self.to_i64().unwrap_or(if Signed::is_negative(self) {
+            IntCode::MIN
+        } else {
+            IntCode::MAX
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn is_negative(&self) -> bool {
+        Signed::is_negative(self)
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        ToPrimitive::to_usize(self)
+    }
+
+    fn offset(&self, base: usize) -> Option<Self> {
+        Some(self + base)
+    }
+
+    fn add_with(&self, other: &Self, _arithmetic: Arithmetic) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn mul_with(&self, other: &Self, _arithmetic: Arithmetic) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+impl From<Memory> for BigExecution {
+    fn from(memory: Memory) -> Self {
+        BigExecution::new(memory.into_iter().map(BigInt::from).collect())
+    }
+}
+
+pub fn parse_big_program(raw_memory: &str) -> result::Result<BigMemory, ParseError> {
+    tokens(raw_memory, &ParseOptions::default())
+        .map(|(index, token)| {
+            token.parse::<BigInt>().map_err(|_| ParseError {
+                index,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::{parse_program, Execution};
+
+    #[test]
+    fn matches_execution() {
+        // the day 9 quine, along with its large number tests
+        for program in &[
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            "1102,34915192,34915192,7,4,7,99,0",
+            "104,1125899906842624,99",
+        ] {
+            let mut execution = Execution::new(parse_program(program));
+            let mut big: BigExecution = parse_program(program).into();
+            assert_eq!(execution.run(), big.run());
+
+            let output: Vec<BigInt> = execution.output.into_iter().map(BigInt::from).collect();
+            assert_eq!(big.output.into_iter().collect::<Vec<_>>(), output);
+        }
+    }
+
+    #[test]
+    fn large_values() {
+        // squares its input twice, well past 64 bits
+        let program = parse_big_program("3,13,2,13,13,13,2,13,13,13,4,13,99,0").unwrap();
+        let input: BigInt = "123456789012".parse().unwrap();
+
+        let mut execution = BigExecution::new_input(program, vec![input.clone()]);
+        assert_eq!(execution.run(), Ok(ExecutionState::Halted));
+        assert_eq!(execution.output.pop_front(), Some(num::pow(input, 4)));
+
+        let mut huge =
+            BigExecution::new(parse_big_program("4,100000000000000000000000,99").unwrap());
+        assert_eq!(huge.run(), Err(CPUError::AddressTooLarge { ip: 0 }));
+
+        // a huge op code doesn't get clamped in to a real one
+        let mut huge = BigExecution::new(parse_big_program("100000000000000000099").unwrap());
+        assert_eq!(
+            huge.run(),
+            Err(CPUError::InvalidOpCode {
+                ip: 0,
+                word: IntCode::MAX
+            })
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::cpu::extensions::Extensions;
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{Execution, Instruction, IntCode, Memory, Mode};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// A program with every word decoded up front, so each execution of it can share the decoding
+/// instead of repeating it. Cloning one is cheap
+#[derive(Debug, Clone)]
+pub struct DecodedProgram(Arc<Decoded>);
+
+#[derive(Debug)]
+struct Decoded {
+    memory: Memory,
+    extensions: Extensions,
+    /// what each word decodes to, whether or not it's really an instruction
+    instructions: Vec<Option<Instruction>>,
+    /// just the modes of each of those, which is all the fast path needs and small enough to stay
+    /// in the CPU's cache alongside memory
+    modes: Vec<Option<[Mode; 3]>>,
+}
+
+impl DecodedProgram {
+    pub fn new(memory: Memory) -> DecodedProgram {
+        DecodedProgram::with_extensions(memory, Extensions::new())
+    }
+
+    pub fn with_extensions(memory: Memory, extensions: Extensions) -> DecodedProgram {
+        let instructions: Vec<Option<Instruction>> = memory
+            .iter()
+            .map(|&word| Instruction::with_extensions(word, &extensions).ok())
+            .collect();
+        let modes = instructions
+            .iter()
+            .map(|instruction| instruction.map(|instruction| instruction.modes))
+            .collect();
+
+        DecodedProgram(Arc::new(Decoded {
+            memory,
+            extensions,
+            instructions,
+            modes,
+        }))
+    }
+
+    pub fn execution(&self) -> Execution {
+        self.execution_input(vec![])
+    }
+
+    pub fn execution_input(&self, input: Memory) -> Execution {
+        self.execution_with_io(input.into(), VecDeque::new())
+    }
+
+    /// A fresh execution of our program, with our extensions and a cache sharing our decoding
+    pub fn execution_with_io<I: Input, O: Output>(&self, input: I, output: O) -> Execution<I, O> {
+        let mut execution = Execution::with_io(self.0.memory.clone(), input, output);
+        execution.extensions = self.0.extensions.clone();
+        execution.cache = Some(InstructionCache::new(self.clone()));
+
+        execution
+    }
+}
+
+/// Decoded instructions by address, shared with every other execution of the same program. We only
+/// use a decoding while the word at its address is still the one the program started with, so
+/// self-modifying programs still run correctly, however memory was written
+#[derive(Debug, Clone)]
+pub struct InstructionCache {
+    program: DecodedProgram,
+    /// only counted once they're asked for, to keep them off the hot path
+    stats: Option<CacheStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl InstructionCache {
+    pub fn new(program: DecodedProgram) -> InstructionCache {
+        InstructionCache {
+            program,
+            stats: None,
+        }
+    }
+
+    /// Counts hits and misses from now on
+    pub fn start_stats(&mut self) {
+        self.stats.get_or_insert_with(CacheStats::default);
+    }
+
+    pub fn stats(&self) -> Option<CacheStats> {
+        self.stats
+    }
+
+    /// The decoding of `word`, if it's still what the program had at `address`
+    #[inline(always)]
+    pub(super) fn get(&mut self, address: usize, word: IntCode) -> Option<Instruction> {
+        let instruction = match self.original(address, word) {
+            true => self.program.0.instructions[address],
+            false => None,
+        };
+        self.count(instruction)
+    }
+
+    /// The modes of `word`, if it's still what the program had at `address`
+    #[inline(always)]
+    pub(super) fn modes(&mut self, address: usize, word: IntCode) -> Option<[Mode; 3]> {
+        let modes = match self.original(address, word) {
+            true => self.program.0.modes[address],
+            false => None,
+        };
+        self.count(modes)
+    }
+
+    #[inline(always)]
+    fn original(&self, address: usize, word: IntCode) -> bool {
+        self.program.0.memory.get(address) == Some(&word)
+    }
+
+    #[inline(always)]
+    fn count<T>(&mut self, decoded: Option<T>) -> Option<T> {
+        if let Some(stats) = self.stats.as_mut() {
+            match decoded {
+                Some(_) => stats.hits += 1,
+                None => stats.misses += 1,
+            }
+        }
+
+        decoded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::{parse_program, ExecutionState};
+
+    #[test]
+    fn self_modifying() {
+        // loops 3 times, turning `ADD [21], #5, [21]` into `MUL [21], #5, [21]` after the first
+        let program = parse_program(
+            "1001,20,1,20,1001,21,5,21,1101,1001,1,4,1007,20,3,22,1005,22,0,99,0,1,0",
+        );
+        let decoded = DecodedProgram::new(program.clone());
+
+        let mut cached = decoded.execution();
+        cached.cache.as_mut().unwrap().start_stats();
+        let mut uncached = Execution::new(program.clone());
+
+        assert_eq!(cached.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(uncached.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(cached[21], 150);
+        assert_eq!(cached.memory, uncached.memory);
+
+        // once address 4 is rewritten it's decoded every time
+        assert_eq!(
+            cached.cache.as_ref().unwrap().stats(),
+            Some(CacheStats {
+                hits: 14,
+                misses: 2
+            })
+        );
+
+        // and the rewrite doesn't leak in to other executions of the program
+        let mut fresh = decoded.execution();
+        fresh.cache.as_mut().unwrap().start_stats();
+        assert_eq!(fresh.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(fresh.memory, uncached.memory);
+        assert_eq!(fresh.cache.as_ref().unwrap().stats().unwrap().misses, 2);
+        assert_eq!(decoded.execution().memory, program);
+    }
+
+    #[test]
+    fn patched_parameters() {
+        let mut execution = Execution::new(parse_program("1101,1,1,5,99,0"));
+        execution.enable_instruction_cache();
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(execution[5], 2);
+
+        execution[2] = 41;
+        execution.ip = 0;
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(execution[5], 42);
+
+        // writing straight to memory is fine too
+        execution.memory[0] = 1102;
+        execution.ip = 0;
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(execution[5], 41);
+    }
+}
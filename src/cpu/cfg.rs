@@ -0,0 +1,322 @@
+use crate::cpu::disassembler::{decode_with, Line, Statement};
+use crate::cpu::extensions::Extensions;
+use crate::cpu::{IntCode, Mode, OpCode};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+/// Where a jump goes, when we can tell without running anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Target {
+    Address(usize),
+    /// targets read from memory or relative to the base, or negative immediates
+    Unresolved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// a jump whose condition is an immediate that always holds
+    Jump,
+    /// a conditional jump being taken
+    Taken,
+    /// running on to the next instruction, including a conditional jump not being taken
+    FallThrough,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edge {
+    /// the start of the block the edge leaves
+    pub from: usize,
+    pub to: Target,
+    pub kind: EdgeKind,
+}
+
+/// Straight line code, only ever entered at its first instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    /// the block ran in to a word that doesn't decode, so it would crash if it got that far
+    pub invalid: bool,
+}
+
+impl BasicBlock {
+    /// The address just past the last instruction
+    pub fn end(&self) -> usize {
+        self.lines
+            .last()
+            .map_or(self.start, |line| line.address + line.size())
+    }
+}
+
+/// The blocks and jumps we can find statically, by following every immediate jump target from the
+/// entry point. Self-modifying code and computed jumps aren't followed, computed jumps are kept as
+/// edges to `Target::Unresolved`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(memory: &[IntCode]) -> ControlFlowGraph {
+        ControlFlowGraph::from_entry(memory, 0)
+    }
+
+    pub fn from_entry(memory: &[IntCode], entry: usize) -> ControlFlowGraph {
+        ControlFlowGraph::with_extensions(memory, entry, &Extensions::new())
+    }
+
+    /// The graph from `entry`, treating the op codes of `extensions` as code rather than data. We
+    /// can't see inside their handlers, so extensions always fall through to the next line
+    pub fn with_extensions(
+        memory: &[IntCode],
+        entry: usize,
+        extensions: &Extensions,
+    ) -> ControlFlowGraph {
+        let leaders = find_leaders(memory, entry, extensions);
+
+        let mut blocks = BTreeMap::new();
+        let mut edges = vec![];
+        for &start in leaders.iter() {
+            let mut block = BasicBlock {
+                start,
+                lines: vec![],
+                invalid: false,
+            };
+
+            let mut address = start;
+            loop {
+                if address != start && leaders.contains(&address) {
+                    edges.push(Edge {
+                        from: start,
+                        to: Target::Address(address),
+                        kind: EdgeKind::FallThrough,
+                    });
+                    break;
+                }
+
+                let line = match line_at(memory, address, extensions) {
+                    Some(line) => line,
+                    None => {
+                        block.invalid = true;
+                        break;
+                    }
+                };
+                address += line.size();
+
+                let exits = exits(&line);
+                block.lines.push(line);
+                if let Some(exits) = exits {
+                    edges.extend(exits.into_iter().map(|(to, kind)| Edge {
+                        from: start,
+                        to,
+                        kind,
+                    }));
+                    break;
+                }
+            }
+
+            blocks.insert(start, block);
+        }
+
+        ControlFlowGraph { blocks, edges }
+    }
+
+    /// The block an address belongs to, if it's code we found
+    pub fn block_containing(&self, address: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end())
+    }
+
+    pub fn successors(&self, start: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == start)
+    }
+
+    pub fn has_unresolved(&self) -> bool {
+        self.edges.iter().any(|edge| edge.to == Target::Unresolved)
+    }
+
+    /// Renders the graph in Graphviz DOT format, with each block's listing as its label
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph intcode {\n    node [shape=box, fontname=monospace];\n");
+
+        for block in self.blocks.values() {
+            let mut label: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", line))
+                .collect();
+            if block.invalid {
+                label.push_str(&format!("{:>5}: ???\\l", block.end()));
+            }
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+
+        if self.has_unresolved() {
+            dot.push_str("    unresolved [label=\"?\", shape=diamond];\n");
+        }
+
+        for edge in self.edges.iter() {
+            let to = match edge.to {
+                Target::Address(address) => format!("b{}", address),
+                Target::Unresolved => "unresolved".to_string(),
+            };
+            let style = match edge.kind {
+                EdgeKind::Jump => "",
+                EdgeKind::Taken => " [label=\"taken\"]",
+                EdgeKind::FallThrough => " [style=dashed]",
+            };
+            writeln!(dot, "    b{} -> {}{};", edge.from, to, style).unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Every address that starts a block, either as the entry, a jump target or after a branch
+fn find_leaders(memory: &[IntCode], entry: usize, extensions: &Extensions) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut queue = VecDeque::new();
+
+    leaders.insert(entry);
+    queue.push_back(entry);
+    while let Some(mut address) = queue.pop_front() {
+        while visited.insert(address) {
+            let line = match line_at(memory, address, extensions) {
+                Some(line) => line,
+                None => break,
+            };
+
+            match exits(&line) {
+                Some(exits) => {
+                    for (to, _) in exits {
+                        if let Target::Address(to) = to {
+                            if leaders.insert(to) {
+                                queue.push_back(to);
+                            }
+                        }
+                    }
+                    break;
+                }
+                None => address += line.size(),
+            }
+        }
+    }
+
+    leaders
+}
+
+fn line_at(memory: &[IntCode], address: usize, extensions: &Extensions) -> Option<Line> {
+    if address >= memory.len() {
+        return None;
+    }
+
+    match decode_with(memory, address, extensions) {
+        Statement::Data(_) => None,
+        statement => Some(Line { address, statement }),
+    }
+}
+
+/// Where control can go after a line that ends a block, or `None` if it just runs on
+fn exits(line: &Line) -> Option<Vec<(Target, EdgeKind)>> {
+    let (instruction, parameters) = match &line.statement {
+        Statement::Instruction(instruction, parameters) => (instruction, parameters),
+        Statement::Data(_) => return None,
+    };
+    let next = Target::Address(line.address + line.size());
+
+    let jumps_when = match instruction.op_code() {
+        OpCode::JumpIfTrue => true,
+        OpCode::JumpIfFalse => false,
+        OpCode::Halt => return Some(vec![]),
+        _ => return None,
+    };
+
+    let modes = instruction.modes();
+    let target = match modes[1] {
+        Mode::Immediate if parameters[1] >= 0 => Target::Address(parameters[1] as usize),
+        _ => Target::Unresolved,
+    };
+
+    Some(match modes[0] {
+        Mode::Immediate if (parameters[0] != 0) == jumps_when => vec![(target, EdgeKind::Jump)],
+        Mode::Immediate => vec![(next, EdgeKind::FallThrough)],
+        _ => vec![(target, EdgeKind::Taken), (next, EdgeKind::FallThrough)],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+
+    #[test]
+    fn blocks_and_edges() {
+        let program = assemble(
+            r#"
+                    IN [count]
+            loop:   ADD [count], #-1, [count]
+                    JT [count], #loop
+                    JF #0, #done
+                    DATA 42
+            done:   OUT [count]
+                    JT #1, [count]
+                    HLT
+            count:  DATA 0
+            "#,
+        )
+        .unwrap();
+
+        let cfg = ControlFlowGraph::new(&program);
+        let starts: Vec<usize> = cfg.blocks.keys().cloned().collect();
+        assert_eq!(starts, vec![0, 2, 9, 13]);
+        assert_eq!(cfg.blocks[&0].lines.len(), 1);
+        assert_eq!(cfg.blocks[&2].end(), 9);
+        assert_eq!(cfg.block_containing(7).unwrap().start, 2);
+        assert_eq!(cfg.block_containing(12), None);
+
+        let edges = |from| {
+            cfg.successors(from)
+                .map(|edge| (edge.to, edge.kind))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edges(0), vec![(Target::Address(2), EdgeKind::FallThrough)]);
+        assert_eq!(
+            edges(2),
+            vec![
+                (Target::Address(2), EdgeKind::Taken),
+                (Target::Address(9), EdgeKind::FallThrough)
+            ]
+        );
+        assert_eq!(edges(9), vec![(Target::Address(13), EdgeKind::Jump)]);
+        assert_eq!(edges(13), vec![(Target::Unresolved, EdgeKind::Jump)]);
+        assert!(cfg.has_unresolved());
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph intcode {"));
+        assert!(
+            dot.contains("    b2 [label=\"    2: ADD [19], #-1, [19]\\l    6: JT [19], #2\\l\"];")
+        );
+        assert!(dot.contains("    b2 -> b2 [label=\"taken\"];"));
+        assert!(dot.contains("    b13 -> unresolved;"));
+    }
+
+    #[test]
+    fn extensions() {
+        let mut extensions = Extensions::new();
+        extensions.register(10, "NOP", &[], |_| Ok(()));
+        let program = vec![10, 10, 99];
+
+        assert!(ControlFlowGraph::new(&program).blocks[&0].invalid);
+
+        let cfg = ControlFlowGraph::with_extensions(&program, 0, &extensions);
+        assert_eq!(cfg.blocks[&0].lines.len(), 3);
+        assert!(!cfg.blocks[&0].invalid);
+    }
+}
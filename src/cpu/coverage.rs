@@ -0,0 +1,205 @@
+use crate::cpu::disassembler::{decode, Line, Statement};
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{Execution, ExecutionState, Instruction, IntCode, OpCode};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Which ways a conditional jump has gone
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Branch {
+    pub taken: bool,
+    pub not_taken: bool,
+}
+
+impl Branch {
+    pub fn is_covered(&self) -> bool {
+        self.taken && self.not_taken
+    }
+}
+
+/// The instructions and branch directions a set of runs executed. Collect one per run and `merge`
+/// them to see what all of the runs covered together
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    executed: BTreeSet<usize>,
+    branches: BTreeMap<usize, Branch>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.executed.contains(&address)
+    }
+
+    /// Every address we executed an instruction at, in order
+    pub fn executed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.executed.iter().cloned()
+    }
+
+    pub fn branch(&self, address: usize) -> Option<&Branch> {
+        self.branches.get(&address)
+    }
+
+    /// Branches we've only seen go one way
+    pub fn uncovered_branches(&self) -> Vec<(usize, Branch)> {
+        self.branches
+            .iter()
+            .filter(|(_, branch)| !branch.is_covered())
+            .map(|(&address, &branch)| (address, branch))
+            .collect()
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        self.executed.extend(other.executed.iter().cloned());
+        for (&address, other) in other.branches.iter() {
+            let branch = self.branches.entry(address).or_default();
+            branch.taken |= other.taken;
+            branch.not_taken |= other.not_taken;
+        }
+    }
+
+    /// `reads` are the values the instruction read, which for a jump starts with its condition
+    pub(super) fn record(
+        &mut self,
+        ip: usize,
+        instruction: &Instruction,
+        state: &ExecutionState,
+        reads: &[IntCode],
+    ) {
+        // an input instruction that's waiting hasn't run yet, it only counts once it gets its input
+        if *state == ExecutionState::NeedsInput {
+            return;
+        }
+
+        self.executed.insert(ip);
+        // a jump to the next instruction still counts as taken
+        let taken = match (instruction.op_code(), reads.first()) {
+            (OpCode::JumpIfTrue, Some(&condition)) => condition != 0,
+            (OpCode::JumpIfFalse, Some(&condition)) => condition == 0,
+            _ => return,
+        };
+        let branch = self.branches.entry(ip).or_default();
+        if taken {
+            branch.taken = true;
+        } else {
+            branch.not_taken = true;
+        }
+    }
+
+    /// Disassembles `memory` marking instructions we never executed with `#####`, like gcov, and
+    /// noting branches that only went one way
+    pub fn listing(&self, memory: &[IntCode]) -> String {
+        let mut lines = vec![];
+
+        let mut address = 0;
+        while address < memory.len() {
+            let mut line = Line {
+                address,
+                statement: decode(memory, address),
+            };
+            // linear decoding can run over an instruction we know executed, stop short of it
+            if (address + 1..address + line.size()).any(|a| self.is_executed(a)) {
+                line.statement = Statement::Data(memory[address]);
+            }
+            address += line.size();
+
+            let marker = match line.statement {
+                Statement::Instruction(..) if !self.is_executed(line.address) => "#####",
+                _ => "",
+            };
+            let note = match self.branch(line.address) {
+                Some(Branch {
+                    taken: true,
+                    not_taken: false,
+                }) => "  ; always taken",
+                Some(Branch {
+                    taken: false,
+                    not_taken: true,
+                }) => "  ; never taken",
+                _ => "",
+            };
+            lines.push(format!("{:>5} {}{}", marker, line, note));
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    pub fn start_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+
+    #[test]
+    fn merged_runs() {
+        // doubles positive input, negates anything else
+        let program = assemble(
+            r#"
+                    IN [value]
+                    LT #0, [value], [positive]
+                    JF [positive], #negate
+                    MUL [value], #2, [value]
+                    OUT [value]
+                    HLT
+            negate: MUL [value], #-1, [value]
+                    OUT [value]
+                    HLT
+            value:  DATA 0
+            positive: DATA 0
+            "#,
+        )
+        .unwrap();
+
+        let run = |input| {
+            let mut execution = Execution::new_input(program.clone(), vec![input]);
+            execution.start_coverage();
+            assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+            execution.take_coverage().unwrap()
+        };
+
+        let mut coverage = run(5);
+        assert_eq!(
+            coverage.executed().collect::<Vec<_>>(),
+            vec![0, 2, 6, 9, 13, 15]
+        );
+        assert_eq!(coverage.uncovered_branches().len(), 1);
+        assert_eq!(
+            coverage.listing(&program).lines().nth(2).unwrap(),
+            "          6: JF [24], #16  ; never taken"
+        );
+        assert_eq!(
+            coverage.listing(&program).lines().nth(6).unwrap(),
+            "#####    16: MUL [23], #-1, [23]"
+        );
+
+        coverage.merge(&run(-5));
+        assert!(coverage.branch(6).unwrap().is_covered());
+        assert!(coverage.uncovered_branches().is_empty());
+        assert!(!coverage.listing(&program).contains("#####"));
+    }
+
+    #[test]
+    fn jump_to_next_instruction() {
+        // both ways land on the HLT, but only a true condition takes the jump
+        let program = assemble("IN [value]\nJT [value], #next\nnext: HLT\nvalue: DATA 0").unwrap();
+
+        let mut execution = Execution::new_input(program.clone(), vec![1]);
+        execution.start_coverage();
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        let coverage = execution.take_coverage().unwrap();
+        assert!(coverage.branch(2).unwrap().taken);
+        assert!(!coverage.branch(2).unwrap().not_taken);
+    }
+}
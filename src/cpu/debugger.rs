@@ -0,0 +1,216 @@
+use crate::cpu::extensions::Role;
+use crate::cpu::{Execution, ExecutionState, OpCode, Parameter, Result};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Watch {
+    fn matches(self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (Watch::ReadWrite, _) | (Watch::Read, Access::Read) | (Watch::Write, Access::Write)
+        )
+    }
+}
+
+/// Why the debugger handed control back to us. Breaks stop before the instruction executes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint(usize, Access),
+    Input,
+    Output,
+    Halted,
+    NeedsInput,
+}
+
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub execution: Execution,
+    pub break_on_input: bool,
+    pub break_on_output: bool,
+    breakpoints: HashSet<usize>,
+    watchpoints: HashMap<usize, Watch>,
+}
+
+impl Debugger {
+    pub fn new(execution: Execution) -> Debugger {
+        Debugger {
+            execution,
+            break_on_input: false,
+            break_on_output: false,
+            breakpoints: HashSet::new(),
+            watchpoints: HashMap::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, ip: usize) {
+        self.breakpoints.insert(ip);
+    }
+
+    pub fn remove_breakpoint(&mut self, ip: usize) -> bool {
+        self.breakpoints.remove(&ip)
+    }
+
+    pub fn breakpoints(&self) -> Vec<usize> {
+        let mut breakpoints: Vec<usize> = self.breakpoints.iter().cloned().collect();
+        breakpoints.sort();
+
+        breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize, watch: Watch) {
+        self.watchpoints.insert(address, watch);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn watchpoints(&self) -> Vec<(usize, Watch)> {
+        let mut watchpoints: Vec<(usize, Watch)> =
+            self.watchpoints.iter().map(|(&a, &w)| (a, w)).collect();
+        watchpoints.sort_by_key(|(address, _)| *address);
+
+        watchpoints
+    }
+
+    /// Executes a single instruction, ignoring any breaks
+    pub fn step(&mut self) -> Result<Stop> {
+        Ok(match self.execution.step()? {
+            ExecutionState::Running => Stop::Stepped,
+            ExecutionState::Halted => Stop::Halted,
+            ExecutionState::NeedsInput => Stop::NeedsInput,
+            ExecutionState::BudgetExhausted => unreachable!("a single step has no budget"),
+        })
+    }
+
+    /// Runs until we hit a break, halt or need input. The current instruction always executes so
+    /// resuming moves past the break we're stopped on.
+    pub fn resume(&mut self) -> Result<Stop> {
+        let mut stop = self.step()?;
+        while stop == Stop::Stepped {
+            stop = match self.check_breaks()? {
+                Some(hit) => hit,
+                None => self.step()?,
+            };
+        }
+
+        Ok(stop)
+    }
+
+    fn check_breaks(&self) -> Result<Option<Stop>> {
+        let ip = self.execution.ip;
+        if self.breakpoints.contains(&ip) {
+            return Ok(Some(Stop::Breakpoint(ip)));
+        }
+
+        let instruction = self.execution.instruction()?;
+        match instruction.op_code() {
+            OpCode::Input if self.break_on_input => return Ok(Some(Stop::Input)),
+            OpCode::Output if self.break_on_output => return Ok(Some(Stop::Output)),
+            _ => (),
+        }
+
+        let roles = instruction.op_code().roles();
+        for (parameter, role) in self.execution.parameters(&instruction)?.iter().zip(roles) {
+            if let Parameter::Address(address) = *parameter {
+                let access = match role {
+                    Role::Read => Access::Read,
+                    Role::Write => Access::Write,
+                };
+
+                match self.watchpoints.get(&address) {
+                    Some(watch) if watch.matches(access) => {
+                        return Ok(Some(Stop::Watchpoint(address, access)));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+    use crate::cpu::Memory;
+
+    fn debugger(source: &str, input: Memory) -> Debugger {
+        Debugger::new(Execution::new_input(assemble(source).unwrap(), input))
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = debugger(
+            r#"
+            loop:   ADD [count], #1, [count]
+                    LT [count], #3, [flag]
+                    JT [flag], #loop
+                    HLT
+            count:  DATA 0
+            flag:   DATA 0
+            "#,
+            vec![],
+        );
+
+        debugger.add_breakpoint(4);
+        assert_eq!(debugger.resume().unwrap(), Stop::Breakpoint(4));
+        assert_eq!(debugger.execution[12], 1);
+        assert_eq!(debugger.resume().unwrap(), Stop::Breakpoint(4));
+        assert_eq!(debugger.execution[12], 2);
+
+        assert!(debugger.remove_breakpoint(4));
+        assert_eq!(debugger.step().unwrap(), Stop::Stepped);
+        assert_eq!(debugger.resume().unwrap(), Stop::Halted);
+        assert_eq!(debugger.execution[12], 3);
+    }
+
+    #[test]
+    fn watchpoints_and_io() {
+        let mut debugger = debugger(
+            r#"
+                    IN [value]
+                    MUL [value], #2, [value]
+                    OUT [value]
+                    HLT
+            value:  DATA 0
+            "#,
+            vec![],
+        );
+
+        debugger.add_watchpoint(9, Watch::Read);
+        debugger.break_on_output = true;
+
+        assert_eq!(debugger.resume().unwrap(), Stop::NeedsInput);
+        debugger.execution.input.push_back(21);
+
+        assert_eq!(
+            debugger.resume().unwrap(),
+            Stop::Watchpoint(9, Access::Read)
+        );
+        assert_eq!(debugger.execution.ip, 2);
+
+        debugger.add_watchpoint(9, Watch::Write);
+        assert_eq!(debugger.resume().unwrap(), Stop::Output);
+        assert_eq!(debugger.execution[9], 42);
+
+        assert_eq!(debugger.resume().unwrap(), Stop::Halted);
+        assert_eq!(debugger.execution.output.pop_front(), Some(42));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::cpu::{Execution, IntCode, Result};
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// Something on the host side that a program talks to by reading and writing memory, addresses are
+/// relative to the start of the range it's attached at
+pub trait Device: Send {
+    fn read(&mut self, offset: usize) -> IntCode;
+
+    fn write(&mut self, offset: usize, value: IntCode);
+
+    /// What a read would see right now, without any of its side effects. This is what traces and
+    /// anything else that's only looking get
+    fn peek(&self, offset: usize) -> IntCode;
+}
+
+pub type SharedDevice = Arc<Mutex<dyn Device>>;
+
+/// Devices mapped over address ranges. Clones of an execution share the same devices, so keep a
+/// handle to anything you want to look at afterwards. Mapped addresses never touch memory, so they
+/// don't count towards the memory limit
+#[derive(Clone, Default)]
+pub struct Devices {
+    mapped: Vec<(Range<usize>, SharedDevice)>,
+}
+
+impl Devices {
+    pub fn new() -> Devices {
+        Devices::default()
+    }
+
+    /// Routes every read and write in `range` to `device`. Instructions are never fetched from a
+    /// device, so don't map over code
+    pub fn attach(&mut self, range: Range<usize>, device: SharedDevice) -> &mut Devices {
+        assert!(
+            self.mapped
+                .iter()
+                .all(|(mapped, _)| range.end <= mapped.start || mapped.end <= range.start),
+            "{:?} overlaps another device",
+            range
+        );
+        self.mapped.push((range, device));
+
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.mapped.is_empty()
+    }
+
+    fn find(&self, address: usize) -> Option<(usize, &SharedDevice)> {
+        self.mapped
+            .iter()
+            .find(|(range, _)| range.contains(&address))
+            .map(|(range, device)| (address - range.start, device))
+    }
+
+    pub fn is_mapped(&self, address: usize) -> bool {
+        self.find(address).is_some()
+    }
+
+    /// Reads from the device mapped at `address`, if there is one
+    pub(super) fn read(&self, address: usize) -> Option<IntCode> {
+        self.find(address)
+            .map(|(offset, device)| lock(device).read(offset))
+    }
+
+    /// Writes to the device mapped at `address`, returning false if there isn't one
+    pub(super) fn write(&self, address: usize, value: IntCode) -> bool {
+        self.find(address)
+            .map(|(offset, device)| lock(device).write(offset, value))
+            .is_some()
+    }
+
+    pub(super) fn peek(&self, address: usize) -> Option<IntCode> {
+        self.find(address)
+            .map(|(offset, device)| lock(device).peek(offset))
+    }
+}
+
+impl Debug for Devices {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.mapped.iter().map(|(range, _)| range))
+            .finish()
+    }
+}
+
+fn lock(device: &SharedDevice) -> std::sync::MutexGuard<'_, dyn Device + 'static> {
+    device.lock().expect("A device panicked")
+}
+
+impl<I, O> Execution<I, O> {
+    /// What the program would see at `address` without disturbing any device mapped there.
+    /// Indexing only sees memory, which is always zero under a device
+    pub fn peek(&self, address: usize) -> IntCode {
+        self.devices.peek(address).unwrap_or_else(|| self[address])
+    }
+
+    /// Reads `address` from whichever device is mapped over it, or from memory if nothing is
+    #[inline(never)]
+    pub(super) fn read_device(&self, address: usize) -> IntCode {
+        self.devices.read(address).unwrap_or_else(|| self[address])
+    }
+
+    /// Writes `value` to whichever device is mapped over `address`, or to memory if nothing is
+    #[inline(never)]
+    pub(super) fn write_device(&mut self, address: usize, value: IntCode) -> Result<()> {
+        if !self.devices.write(address, value) {
+            *self.cell_mut(address)? = value;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+    use crate::cpu::ExecutionState;
+
+    /// Counts up every time it's read
+    struct Clock(IntCode);
+
+    impl Device for Clock {
+        fn read(&mut self, _offset: usize) -> IntCode {
+            self.0 += 1;
+            self.0
+        }
+
+        fn write(&mut self, _offset: usize, value: IntCode) {
+            self.0 = value;
+        }
+
+        fn peek(&self, _offset: usize) -> IntCode {
+            self.0
+        }
+    }
+
+    /// Draws a pixel whenever its third register is written
+    #[derive(Default)]
+    struct Screen {
+        registers: [IntCode; 2],
+        pixels: Vec<(IntCode, IntCode, IntCode)>,
+    }
+
+    impl Device for Screen {
+        fn read(&mut self, offset: usize) -> IntCode {
+            self.peek(offset)
+        }
+
+        fn write(&mut self, offset: usize, value: IntCode) {
+            match offset {
+                0 | 1 => self.registers[offset] = value,
+                _ => self
+                    .pixels
+                    .push((self.registers[0], self.registers[1], value)),
+            }
+        }
+
+        fn peek(&self, offset: usize) -> IntCode {
+            self.registers.get(offset).cloned().unwrap_or(0)
+        }
+    }
+
+    #[test]
+    fn mapped_devices() {
+        // draws the clock at (clock, 3) a few times
+        let program = assemble(
+            r#"
+                    ADD #0, #10, [1000]
+            loop:   ADD [1000], #0, [2000]
+                    ADD #0, #3, [2001]
+                    IN [2002]
+                    LT [1000], #15, [more]
+                    JT [more], #loop
+                    OUT [2000]
+                    HLT
+            more:   DATA 0
+            "#,
+        )
+        .unwrap();
+
+        let clock = Arc::new(Mutex::new(Clock(0)));
+        let screen = Arc::new(Mutex::new(Screen::default()));
+        let mut execution = Execution::new_input(program, vec![7, 8, 9]);
+        execution
+            .devices
+            .attach(1000..1001, clock.clone())
+            .attach(2000..2003, screen.clone());
+
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(
+            screen.lock().unwrap().pixels,
+            vec![(11, 3, 7), (13, 3, 8), (15, 3, 9)]
+        );
+        assert_eq!(execution.output, vec![15]);
+        assert_eq!(clock.lock().unwrap().0, 16);
+    }
+
+    #[test]
+    fn devices_bypass_memory() {
+        let program = assemble("ADD #5, #0, [1000000]\nOUT [1000000]\nHLT").unwrap();
+        let clock = Arc::new(Mutex::new(Clock(0)));
+        let mut execution = Execution::new(program.clone());
+        execution.memory_limit = Some(program.len());
+        execution
+            .devices
+            .attach(1_000_000..1_000_001, clock.clone());
+        execution.start_trace();
+
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(execution.output, vec![6]);
+        assert_eq!(execution.memory, program);
+        assert_eq!(execution.pages.words(), 0);
+        assert_eq!(execution[1_000_000], 0);
+        assert_eq!(execution.peek(1_000_000), 6);
+
+        let trace = execution.take_trace().unwrap();
+        assert_eq!(
+            trace.entries()[0].to_string(),
+            "    0: ADD 5 0 -> [1000000]=5"
+        );
+    }
+
+    #[test]
+    fn traced_device_reads() {
+        // the clock ticks on every read, so each read sees a different value
+        let program = assemble("OUT [1000]\nADD [1000], [1000], [sum]\nHLT\nsum: DATA 0").unwrap();
+        let mut execution = Execution::new(program);
+        execution
+            .devices
+            .attach(1000..1001, Arc::new(Mutex::new(Clock(0))));
+        execution.start_trace();
+
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(execution.output, vec![1]);
+        assert_eq!(
+            execution.take_trace().unwrap().to_string(),
+            "    0: OUT 1 out=1\n    2: ADD 2 3 -> [7]=5\n    6: HLT\n"
+        );
+    }
+}
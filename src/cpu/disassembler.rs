@@ -0,0 +1,138 @@
+use crate::cpu::extensions::Extensions;
+use crate::cpu::{Instruction, IntCode, Mode};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Instruction(Instruction, Vec<IntCode>),
+    Data(IntCode),
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Instruction(instruction, parameters) => {
+                f.write_str(instruction.op_code().mnemonic())?;
+                for (i, (mode, value)) in instruction.modes().iter().zip(parameters).enumerate() {
+                    f.write_str(if i == 0 { " " } else { ", " })?;
+                    write_parameter(f, mode, *value)?;
+                }
+
+                Ok(())
+            }
+            Statement::Data(value) => write!(f, "DATA {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    pub statement: Statement,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match &self.statement {
+            Statement::Instruction(_, parameters) => parameters.len() + 1,
+            Statement::Data(_) => 1,
+        }
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}: {}", self.address, self.statement)
+    }
+}
+
+/// Linearly decodes memory, any word that isn't a complete canonical instruction becomes `DATA`
+pub fn disassemble(memory: &[IntCode]) -> Vec<Line> {
+    disassemble_with(memory, &Extensions::new())
+}
+
+/// Disassembles `memory`, decoding the op codes of `extensions` too
+pub fn disassemble_with(memory: &[IntCode], extensions: &Extensions) -> Vec<Line> {
+    let mut lines = vec![];
+
+    let mut address = 0;
+    while address < memory.len() {
+        let line = Line {
+            address,
+            statement: decode_with(memory, address, extensions),
+        };
+        address += line.size();
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub fn listing(memory: &[IntCode]) -> String {
+    listing_with(memory, &Extensions::new())
+}
+
+pub fn listing_with(memory: &[IntCode], extensions: &Extensions) -> String {
+    disassemble_with(memory, extensions)
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decodes the statement at `address`, anything that isn't a complete canonical instruction is `DATA`
+pub fn decode(memory: &[IntCode], address: usize) -> Statement {
+    decode_with(memory, address, &Extensions::new())
+}
+
+pub fn decode_with(memory: &[IntCode], address: usize, extensions: &Extensions) -> Statement {
+    let word = memory[address];
+
+    if let Ok(instruction) = Instruction::with_extensions(word, extensions) {
+        let end = address + 1 + instruction.op_code().parameter_count();
+
+        // words with extra mode digits wouldn't survive a round trip, so treat them as data
+        if instruction.encode() == word && end <= memory.len() {
+            return Statement::Instruction(instruction, memory[address + 1..end].to_vec());
+        }
+    }
+
+    Statement::Data(word)
+}
+
+fn write_parameter(f: &mut Formatter<'_>, mode: &Mode, value: IntCode) -> fmt::Result {
+    match mode {
+        Mode::Position => write!(f, "[{}]", value),
+        Mode::Immediate => write!(f, "#{}", value),
+        Mode::Relative => write!(f, "rb{:+}", value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn disassemble_modes() {
+        assert_eq!(
+            listing(&parse_program("1002,4,3,4,33")),
+            "    0: MUL [4], #3, [4]\n    4: DATA 33"
+        );
+        assert_eq!(
+            listing(&parse_program("109,-1,204,1,99")),
+            "    0: ARB #-1\n    2: OUT rb+1\n    4: HLT"
+        );
+    }
+
+    #[test]
+    fn disassemble_data() {
+        // unknown op codes, extra mode digits and truncated instructions
+        assert_eq!(
+            listing(&parse_program("42,1099,-1,1,2")),
+            "    0: DATA 42\n    1: DATA 1099\n    2: DATA -1\n    3: DATA 1\n    4: DATA 2"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::cpu::interpreter;
+use crate::cpu::interpreter::Machine;
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{CPUError, DecodeError, Execution, Instruction, IntCode, OpCode, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::result;
+use std::sync::Arc;
+
+/// How an extension uses each of its parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Role {
+    Read,
+    Write,
+}
+
+/// What a handler sees of its instruction: the values of its read parameters, and somewhere to put
+/// its writes and where to jump
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operands {
+    roles: &'static [Role],
+    values: Vec<IntCode>,
+    writes: Vec<Option<IntCode>>,
+    jump: Option<IntCode>,
+}
+
+impl Operands {
+    /// The value of a read parameter, or 0 for a write parameter
+    pub fn read(&self, parameter: usize) -> IntCode {
+        self.values[parameter]
+    }
+
+    /// Writes through a write parameter once the handler returns, writes we skip leave memory alone.
+    /// Fails if `parameter` isn't a write parameter, so handlers can pass it on with `?`
+    pub fn write(&mut self, parameter: usize, value: IntCode) -> result::Result<(), String> {
+        match self.roles.get(parameter) {
+            Some(Role::Write) => {
+                self.writes[parameter] = Some(value);
+                Ok(())
+            }
+            _ => Err(format!(
+                "parameter {} isn't a write parameter",
+                parameter + 1
+            )),
+        }
+    }
+
+    /// Continues at `address` instead of the next instruction
+    pub fn jump(&mut self, address: IntCode) {
+        self.jump = Some(address);
+    }
+}
+
+pub type Handler = Arc<dyn Fn(&mut Operands) -> result::Result<(), String> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Extension {
+    pub code: IntCode,
+    pub mnemonic: &'static str,
+    pub roles: &'static [Role],
+    handler: Handler,
+}
+
+impl Extension {
+    pub fn op_code(&self) -> OpCode {
+        OpCode::Extension {
+            code: self.code,
+            mnemonic: self.mnemonic,
+            roles: self.roles,
+        }
+    }
+}
+
+impl Debug for Extension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extension")
+            .field("code", &self.code)
+            .field("mnemonic", &self.mnemonic)
+            .field("roles", &self.roles)
+            .finish()
+    }
+}
+
+/// Op codes on top of the standard set, shared between every clone of an execution
+#[derive(Debug, Clone, Default)]
+pub struct Extensions {
+    /// `None` until something is registered, so executions without extensions don't allocate
+    extensions: Option<Arc<HashMap<IntCode, Extension>>>,
+}
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Adds an op code, panicking if `code` is already taken or can't fit in an instruction word.
+    /// Parameters are decoded with the usual modes, so there can only be 3 of them
+    pub fn register<F>(
+        &mut self,
+        code: IntCode,
+        mnemonic: &'static str,
+        roles: &'static [Role],
+        handler: F,
+    ) -> &mut Extensions
+    where
+        F: Fn(&mut Operands) -> result::Result<(), String> + Send + Sync + 'static,
+    {
+        assert!(
+            (1..99).contains(&code) && OpCode::new(code).is_err(),
+            "op code {} is reserved",
+            code
+        );
+        assert!(roles.len() <= 3, "{} has more than 3 parameters", mnemonic);
+        assert!(
+            self.get(code).is_none(),
+            "op code {} is already registered",
+            code
+        );
+
+        Arc::make_mut(self.extensions.get_or_insert_with(Default::default)).insert(
+            code,
+            Extension {
+                code,
+                mnemonic,
+                roles,
+                handler: Arc::new(handler),
+            },
+        );
+
+        self
+    }
+
+    pub fn get(&self, code: IntCode) -> Option<&Extension> {
+        self.extensions.as_ref()?.get(&code)
+    }
+
+    /// Everything registered, by op code
+    pub fn registered(&self) -> Vec<&Extension> {
+        let mut registered: Vec<&Extension> = self
+            .extensions
+            .iter()
+            .flat_map(|extensions| extensions.values())
+            .collect();
+        registered.sort_by_key(|extension| extension.code);
+
+        registered
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_none()
+    }
+
+    /// Decodes the op code of `word`, checking our extensions for anything non-standard
+    #[inline(always)]
+    pub fn op_code(&self, word: IntCode) -> result::Result<OpCode, DecodeError> {
+        OpCode::new(word).or_else(|e| self.get(word % 100).map(Extension::op_code).ok_or(e))
+    }
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    /// The extension an instruction runs
+    pub(super) fn registered_extension(&self, instruction: &Instruction) -> Result<Extension> {
+        let code = instruction.op_code().code();
+        self.extensions
+            .get(code)
+            .cloned()
+            .ok_or(CPUError::InvalidOpCode {
+                ip: self.ip,
+                word: code,
+            })
+    }
+}
+
+impl Extension {
+    /// Runs us on `machine`, returning where we jump to if we do
+    pub(super) fn call<M: Machine<Word = IntCode>>(
+        &self,
+        machine: &mut M,
+        instruction: &Instruction,
+    ) -> Result<Option<usize>> {
+        let mut operands = Operands {
+            roles: self.roles,
+            values: vec![0; self.roles.len()],
+            writes: vec![None; self.roles.len()],
+            jump: None,
+        };
+        for (i, role) in self.roles.iter().enumerate() {
+            if *role == Role::Read {
+                operands.values[i] = interpreter::read(machine, instruction, i)?;
+            }
+        }
+
+        (self.handler)(&mut operands).map_err(|message| CPUError::Extension {
+            ip: machine.ip(),
+            mnemonic: self.mnemonic,
+            message,
+        })?;
+
+        for (i, write) in operands.writes.iter().enumerate() {
+            if let Some(value) = write {
+                interpreter::write(machine, instruction, i, *value)?;
+            }
+        }
+
+        operands
+            .jump
+            .map(|address| interpreter::address(machine.ip(), &address))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::{parse_program, ExecutionState};
+    use std::sync::Mutex;
+
+    #[test]
+    fn extensions() {
+        let traps = Arc::new(Mutex::new(vec![]));
+        let trapped = traps.clone();
+
+        let mut extensions = Extensions::new();
+        extensions
+            .register(
+                10,
+                "DIV",
+                &[Role::Read, Role::Read, Role::Write],
+                |operands| match operands.read(1) {
+                    0 => Err("division by zero".to_string()),
+                    divisor => operands.write(2, operands.read(0) / divisor),
+                },
+            )
+            .register(11, "TRAP", &[Role::Read], move |operands| {
+                trapped.lock().unwrap().push(operands.read(0));
+                Ok(())
+            });
+
+        let run = |program: &str| {
+            let mut execution = Execution::new(parse_program(program));
+            execution.extensions = extensions.clone();
+            execution.run().map(|_| execution)
+        };
+
+        // DIV [0], #7, [0]; TRAP [0]; TRAP rb+0
+        let execution = run("1010,0,7,0,11,0,211,0,99").unwrap();
+        assert_eq!(execution[0], 144);
+        assert_eq!(*traps.lock().unwrap(), vec![144, 144]);
+
+        let error = run("1110,1,0,0,99").unwrap_err();
+        assert_eq!(error.to_string(), "DIV at ip 0 failed: division by zero");
+
+        // a handler writing through a read parameter is an error, not a panic
+        let mut bad = Extensions::new();
+        bad.register(10, "BAD", &[Role::Read], |operands| operands.write(0, 1));
+        let mut execution = Execution::new(parse_program("10,0,99"));
+        execution.extensions = bad;
+        assert_eq!(
+            execution.run().unwrap_err().to_string(),
+            "BAD at ip 0 failed: parameter 1 isn't a write parameter"
+        );
+
+        // unregistered op codes still fail
+        let mut execution = Execution::new(parse_program("12,0,99"));
+        execution.extensions = extensions;
+        assert_eq!(
+            execution.run(),
+            Err(CPUError::InvalidOpCode { ip: 0, word: 12 })
+        );
+        execution.memory[0] = 11;
+        assert_eq!(
+            execution.instruction().unwrap().op_code().mnemonic(),
+            "TRAP"
+        );
+        assert_eq!(execution.run(), Ok(ExecutionState::Halted));
+    }
+
+    #[test]
+    fn multiple_writes() {
+        use crate::cpu::debugger::{Access, Debugger, Stop, Watch};
+        use crate::cpu::history::History;
+
+        let mut extensions = Extensions::new();
+        extensions.register(
+            10,
+            "DIVMOD",
+            &[Role::Read, Role::Write, Role::Write],
+            |operands| {
+                operands.write(1, operands.read(0) / 7)?;
+                operands.write(2, operands.read(0) % 7)
+            },
+        );
+        // ADD #1, #1, [9]; DIVMOD #23, [10], [11]
+        let mut execution = Execution::new(parse_program("1101,1,1,9,110,23,10,11,99,0,0,0"));
+        execution.extensions = extensions;
+
+        let mut traced = execution.clone();
+        traced.start_trace();
+        traced.run().unwrap();
+        assert_eq!(
+            traced.take_trace().unwrap().entries()[1].to_string(),
+            "    4: DIVMOD 23 -> [10]=3 -> [11]=2"
+        );
+
+        let mut debugger = Debugger::new(execution.clone());
+        debugger.add_watchpoint(11, Watch::Write);
+        assert_eq!(debugger.resume(), Ok(Stop::Watchpoint(11, Access::Write)));
+
+        let mut history = History::new(execution, 10);
+        history.run().unwrap();
+        assert_eq!(history.execution.memory[9..], [2, 3, 2]);
+        assert_eq!(history.step_back(1), 1);
+        assert_eq!(history.execution.memory[9..], [2, 0, 0]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::cpu::interpreter;
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{Execution, ExecutionState, IntCode, Mode, Plain, Result};
+use std::convert::TryFrom;
+
+/// How far a fast step got
+enum Fast {
+    Done(ExecutionState),
+    /// an input instruction that writes to this address, for us to read and handle its errors
+    Input(usize),
+    /// something the interpreter has to do, we haven't changed anything
+    Slow,
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    /// Runs a plain execution for at most `budget` instructions, with the standard op codes worked
+    /// out inline on `IntCode`s. Anything out of the ordinary, like errors, overflow, growing memory
+    /// or extensions, goes to the interpreter a single instruction at a time, so it's still the one
+    /// place that says what an instruction does
+    pub(super) fn run_fast<const CACHED: bool>(&mut self, budget: usize) -> Result<ExecutionState> {
+        for _ in 0..budget {
+            let state = match self.fast_step::<CACHED>() {
+                Fast::Done(state) => state,
+                Fast::Input(address) => match self.input.read()? {
+                    Some(value) => {
+                        self.memory[address] = value;
+                        self.ip += 2;
+                        ExecutionState::Running
+                    }
+                    None => ExecutionState::NeedsInput,
+                },
+                Fast::Slow => interpreter::step(&mut Plain::<_, _, CACHED>(self))?,
+            };
+            if state != ExecutionState::Running {
+                return Ok(state);
+            }
+        }
+
+        Ok(ExecutionState::BudgetExhausted)
+    }
+
+    /// Executes the instruction at our ip, unless it's input or the interpreter has to
+    #[inline(always)]
+    fn fast_step<const CACHED: bool>(&mut self) -> Fast {
+        self.try_fast_step::<CACHED>().unwrap_or(Fast::Slow)
+    }
+
+    #[inline(always)]
+    fn try_fast_step<const CACHED: bool>(&mut self) -> Option<Fast> {
+        let ip = self.ip;
+        let (op_code, modes) = self.fast_decode::<CACHED>(ip)?;
+
+        // the standard op codes, straight from the word since that's cheaper than an `OpCode`
+        self.ip = match op_code {
+            1 | 2 | 7 | 8 => {
+                let a = self.fast_read(ip, &modes, 0)?;
+                let b = self.fast_read(ip, &modes, 1)?;
+                let address = self.fast_address(ip, &modes, 2)?;
+                let value = match op_code {
+                    1 => a.checked_add(b)?,
+                    2 => a.checked_mul(b)?,
+                    7 => (a < b) as IntCode,
+                    _ => (a == b) as IntCode,
+                };
+                *self.memory.get_mut(address)? = value;
+                ip + 4
+            }
+            3 => {
+                let address = self.fast_address(ip, &modes, 0)?;
+                return (address < self.memory.len()).then_some(Fast::Input(address));
+            }
+            4 => {
+                let value = self.fast_read(ip, &modes, 0)?;
+                self.output.write(value);
+                ip + 2
+            }
+            5 | 6 => {
+                let condition = self.fast_read(ip, &modes, 0)?;
+                if (condition != 0) == (op_code == 5) {
+                    usize::try_from(self.fast_read(ip, &modes, 1)?).ok()?
+                } else {
+                    ip + 3
+                }
+            }
+            9 => {
+                let adjustment = self.fast_read(ip, &modes, 0)?;
+                self.relative_base = self.fast_relative(adjustment)?;
+                ip + 2
+            }
+            99 => return Some(Fast::Done(ExecutionState::Halted)),
+            _ => return None,
+        };
+
+        Some(Fast::Done(ExecutionState::Running))
+    }
+
+    /// The op code and modes of the instruction at `ip`
+    #[inline(always)]
+    fn fast_decode<const CACHED: bool>(&mut self, ip: usize) -> Option<(IntCode, [Mode; 3])> {
+        let word = *self.memory.get(ip)?;
+        if CACHED {
+            if let Some(modes) = self.cache.as_mut().and_then(|cache| cache.modes(ip, word)) {
+                return Some((word % 100, modes));
+            }
+        }
+
+        let modes = [
+            Mode::new(word / 100 % 10)?,
+            Mode::new(word / 1000 % 10)?,
+            Mode::new(word / 10000 % 10)?,
+        ];
+
+        Some((word % 100, modes))
+    }
+
+    #[inline(always)]
+    fn fast_read(&self, ip: usize, modes: &[Mode; 3], offset: usize) -> Option<IntCode> {
+        let value = *self.memory.get(ip + 1 + offset)?;
+        match modes[offset] {
+            Mode::Immediate => Some(value),
+            _ => Some(self[self.fast_address(ip, modes, offset)?]),
+        }
+    }
+
+    #[inline(always)]
+    fn fast_address(&self, ip: usize, modes: &[Mode; 3], offset: usize) -> Option<usize> {
+        let value = *self.memory.get(ip + 1 + offset)?;
+        match modes[offset] {
+            Mode::Position => usize::try_from(value).ok(),
+            Mode::Immediate => None,
+            Mode::Relative => self.fast_relative(value),
+        }
+    }
+
+    /// `relative_base + value`, as long as it's a valid address
+    #[inline(always)]
+    fn fast_relative(&self, value: IntCode) -> Option<usize> {
+        let relative_base = IntCode::try_from(self.relative_base).ok()?;
+        usize::try_from(relative_base.checked_add(value)?).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::extensions::{Extensions, Role};
+    use crate::cpu::{parse_program, Arithmetic};
+
+    /// Runs `execution` both ways, they should end up in the same place
+    fn check(execution: Execution) {
+        let mut fast = execution.clone();
+        let mut stepped = execution;
+
+        let expected = loop {
+            match stepped.step() {
+                Ok(ExecutionState::Running) => (),
+                state => break state,
+            }
+        };
+        assert_eq!(fast.run(), expected);
+        assert_eq!(
+            (fast.ip, fast.relative_base, &fast.memory, &fast.output),
+            (
+                stepped.ip,
+                stepped.relative_base,
+                &stepped.memory,
+                &stepped.output
+            )
+        );
+    }
+
+    #[test]
+    fn matches_interpreter() {
+        let programs = [
+            // day 9's quine, and its tests
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            "1102,34915192,34915192,7,4,7,99,0",
+            // negative addresses, in all their forms
+            "1,-1,0,0,99",
+            "109,-5,204,1,99",
+            "1105,1,-3",
+            "109,-1,99",
+            // writing to an immediate, and past the end of memory
+            "1101,1,1,1,99",
+            "1101,1,1,100,4,100,99",
+            // a bad mode, and an op code we don't know
+            "30001,0,0,0,99",
+            "42",
+            // running off the end of memory
+            "1101,1,1,0",
+            // waiting for input, twice
+            "3,0,3,1,99",
+            // overflow
+            "1102,4611686018427387904,2,0,4,0,99",
+        ];
+
+        for program in programs.iter() {
+            for &arithmetic in [
+                Arithmetic::Wrapping,
+                Arithmetic::Checked,
+                Arithmetic::Saturating,
+            ]
+            .iter()
+            {
+                let mut execution = Execution::new_input(parse_program(program), vec![7]);
+                execution.arithmetic = arithmetic;
+                check(execution.clone());
+
+                execution.enable_instruction_cache();
+                check(execution);
+            }
+        }
+    }
+
+    #[test]
+    fn extensions_and_memory_limit() {
+        let mut extensions = Extensions::new();
+        extensions.register(10, "DOUBLE", &[Role::Read, Role::Write], |operands| {
+            operands.write(1, operands.read(0) * 2)
+        });
+        let mut execution = Execution::new(parse_program("10,6,7,1101,0,0,21,99"));
+        execution.extensions = extensions;
+        check(execution);
+
+        let mut execution = Execution::new(parse_program("1101,1,1,100,99"));
+        execution.memory_limit = Some(10);
+        check(execution);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::cpu::{Execution, ExecutionState, IntCode, OpCode, Parameter, Result};
+use std::collections::VecDeque;
+
+/// Everything needed to put an execution back the way it was before a single instruction
+#[derive(Debug, Clone)]
+struct Undo {
+    ip: usize,
+    relative_base: usize,
+    memory_len: usize,
+    /// every address the instruction writes to, along with what was there before
+    writes: Vec<(usize, IntCode)>,
+    /// written addresses whose pages weren't allocated yet
+    unallocated: Vec<usize>,
+    input: Option<IntCode>,
+    output: bool,
+}
+
+/// Wraps an execution with a bounded undo log so we can walk backwards from a bad result
+#[derive(Debug, Clone)]
+pub struct History {
+    pub execution: Execution,
+    undo: VecDeque<Undo>,
+    capacity: usize,
+    instruction_count: usize,
+}
+
+impl History {
+    /// Keeps at most `capacity` instructions of history
+    pub fn new(execution: Execution, capacity: usize) -> History {
+        History {
+            execution,
+            undo: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+            instruction_count: 0,
+        }
+    }
+
+    /// How many instructions have been executed, not counting ones we stepped back over
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    /// The earliest instruction count we can still rewind to
+    pub fn earliest(&self) -> usize {
+        self.instruction_count - self.undo.len()
+    }
+
+    pub fn run(&mut self) -> Result<ExecutionState> {
+        let mut state = self.step()?;
+        while state == ExecutionState::Running {
+            state = self.step()?;
+        }
+
+        Ok(state)
+    }
+
+    pub fn step(&mut self) -> Result<ExecutionState> {
+        let execution = &self.execution;
+        let instruction = execution.instruction()?;
+        let op_code = *instruction.op_code();
+
+        let parameters = execution.parameters(&instruction)?;
+        // devices don't keep their history, so there's nothing we could put back for them
+        let write_addresses: Vec<usize> = op_code
+            .write_parameters()
+            .filter_map(|i| match parameters[i] {
+                Parameter::Address(address) => Some(address),
+                Parameter::Immediate(_) => None,
+            })
+            .filter(|&address| !execution.devices.is_mapped(address))
+            .collect();
+
+        let input = execution.input.front().cloned();
+        let mut undo = Undo {
+            ip: execution.ip,
+            relative_base: execution.relative_base,
+            memory_len: execution.memory.len(),
+            writes: write_addresses
+                .iter()
+                .map(|&address| (address, execution[address]))
+                .collect(),
+            unallocated: write_addresses
+                .iter()
+                .cloned()
+                .filter(|&address| !execution.pages.is_allocated(address))
+                .collect(),
+            input: None,
+            output: false,
+        };
+
+        let state = self.execution.step()?;
+
+        // halting or starving leaves everything untouched, so there is nothing to undo
+        if state == ExecutionState::Running {
+            match op_code {
+                OpCode::Input => undo.input = input,
+                OpCode::Output => undo.output = true,
+                _ => (),
+            }
+
+            if self.capacity > 0 {
+                if self.undo.len() == self.capacity {
+                    self.undo.pop_front();
+                }
+                self.undo.push_back(undo);
+            }
+            self.instruction_count += 1;
+        }
+
+        Ok(state)
+    }
+
+    /// Undoes up to `count` instructions, returning how many we actually stepped back
+    pub fn step_back(&mut self, count: usize) -> usize {
+        for stepped in 0..count {
+            let undo = match self.undo.pop_back() {
+                Some(undo) => undo,
+                None => return stepped,
+            };
+
+            let execution = &mut self.execution;
+            execution.ip = undo.ip;
+            execution.relative_base = undo.relative_base;
+            // in reverse, in case an instruction wrote to the same address twice
+            for &(address, value) in undo.writes.iter().rev() {
+                execution[address] = value;
+            }
+            execution.memory.truncate(undo.memory_len);
+            for &address in undo.unallocated.iter() {
+                execution.pages.free(address);
+            }
+            if let Some(input) = undo.input {
+                execution.input.push_front(input);
+            }
+            if undo.output {
+                execution.output.pop_back();
+            }
+
+            self.instruction_count -= 1;
+        }
+
+        count
+    }
+
+    /// Rewinds to the state right after `instruction_count` instructions executed, returning
+    /// false if that's further back than our history goes
+    pub fn rewind_to(&mut self, instruction_count: usize) -> bool {
+        if instruction_count < self.earliest() || instruction_count > self.instruction_count {
+            false
+        } else {
+            self.step_back(self.instruction_count - instruction_count);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::{parse_program, CONTIGUOUS_LIMIT};
+
+    #[test]
+    fn step_back() {
+        let program = parse_program("3,13,1,13,13,100,4,100,4,13,99,0,0,0");
+        let mut history = History::new(Execution::new_input(program.clone(), vec![21]), 100);
+
+        assert_eq!(history.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(history.instruction_count(), 4);
+        assert_eq!(Vec::from(history.execution.output.clone()), vec![42, 21]);
+
+        assert_eq!(history.step_back(1), 1);
+        assert_eq!(history.execution.ip, 8);
+        assert_eq!(Vec::from(history.execution.output.clone()), vec![42]);
+
+        assert!(history.rewind_to(1));
+        assert_eq!(history.execution.ip, 2);
+        assert_eq!(history.execution[100], 0);
+
+        assert_eq!(history.step_back(5), 1);
+        assert_eq!(history.execution.memory, program);
+        assert_eq!(Vec::from(history.execution.input.clone()), vec![21]);
+        assert!(history.execution.output.is_empty());
+
+        // replaying forwards gets us back to the same place
+        assert_eq!(history.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(Vec::from(history.execution.output.clone()), vec![42, 21]);
+    }
+
+    #[test]
+    fn sparse_writes() {
+        // writes past the contiguous limit, allocating a page
+        let address = CONTIGUOUS_LIMIT + 10;
+        let program = parse_program(&format!("1101,1,2,{0},4,{0},99", address));
+        let mut history = History::new(Execution::new(program.clone()), 10);
+        assert_eq!(history.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(history.execution.pages.cells(), vec![(address, 3)]);
+
+        assert!(history.rewind_to(0));
+        assert_eq!(history.execution.pages.words(), 0);
+        assert_eq!(history.execution.memory, program);
+
+        // the next run allocates the page again
+        assert_eq!(history.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(history.execution.pages.cells(), vec![(address, 3)]);
+    }
+
+    #[test]
+    fn bounded_history() {
+        // count up in address 11 until it reaches 10
+        let program = parse_program("1001,11,1,11,1007,11,10,12,1005,12,0,0,0,99");
+        let mut history = History::new(Execution::new(program), 5);
+
+        for _ in 0..12 {
+            history.step().unwrap();
+        }
+
+        assert_eq!(history.instruction_count(), 12);
+        assert_eq!(history.earliest(), 7);
+        assert!(!history.rewind_to(6));
+        assert!(history.rewind_to(7));
+        assert_eq!(history.step_back(1), 0);
+    }
+}
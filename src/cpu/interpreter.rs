@@ -0,0 +1,284 @@
+use crate::cpu::{
+    Arithmetic, CPUError, ExecutionState, Instruction, IntCode, Mode, OpCode, Parameter, Result,
+};
+use std::convert::TryFrom;
+
+/// A word of memory the interpreter can run on, `IntCode` for everything but `BigExecution`
+pub(super) trait Word: Clone + PartialEq + PartialOrd {
+    fn from_int(value: IntCode) -> Self;
+
+    /// The closest `IntCode`, for decoding instructions and reporting errors
+    fn saturate(&self) -> IntCode;
+
+    fn is_zero(&self) -> bool;
+
+    fn is_negative(&self) -> bool;
+
+    /// `None` if this doesn't fit in a `usize`, which includes anything negative
+    fn to_usize(&self) -> Option<usize>;
+
+    /// `self + base`, `None` if that overflows
+    fn offset(&self, base: usize) -> Option<Self>;
+
+    /// `None` if `arithmetic` is checked and the sum overflows
+    fn add_with(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+
+    /// `None` if `arithmetic` is checked and the product overflows
+    fn mul_with(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+}
+
+impl Word for IntCode {
+    #[inline]
+    fn from_int(value: IntCode) -> Self {
+        value
+    }
+
+    #[inline]
+    fn saturate(&self) -> IntCode {
+        *self
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    #[inline]
+    fn is_negative(&self) -> bool {
+        *self < 0
+    }
+
+    #[inline]
+    fn to_usize(&self) -> Option<usize> {
+        usize::try_from(*self).ok()
+    }
+
+    #[inline]
+    fn offset(&self, base: usize) -> Option<Self> {
+        IntCode::try_from(base).ok()?.checked_add(*self)
+    }
+
+    #[inline]
+    fn add_with(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self> {
+        match arithmetic {
+            Arithmetic::Wrapping => Some(self.wrapping_add(*other)),
+            Arithmetic::Checked => self.checked_add(*other),
+            Arithmetic::Saturating => Some(self.saturating_add(*other)),
+        }
+    }
+
+    #[inline]
+    fn mul_with(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self> {
+        match arithmetic {
+            Arithmetic::Wrapping => Some(self.wrapping_mul(*other)),
+            Arithmetic::Checked => self.checked_mul(*other),
+            Arithmetic::Saturating => Some(self.saturating_mul(*other)),
+        }
+    }
+}
+
+/// Everything the interpreter needs from whatever it's running on
+pub(super) trait Machine {
+    type Word: Word;
+
+    fn ip(&self) -> usize;
+
+    fn set_ip(&mut self, ip: usize);
+
+    fn relative_base(&self) -> usize;
+
+    fn set_relative_base(&mut self, relative_base: usize);
+
+    fn arithmetic(&self) -> Arithmetic {
+        Arithmetic::default()
+    }
+
+    /// Decodes the instruction at our ip
+    fn decode(&mut self) -> Result<Instruction>;
+
+    /// A word of an instruction, which always comes from memory
+    fn fetch(&self, address: usize) -> Self::Word;
+
+    /// A word a parameter reads
+    fn load(&self, address: usize) -> Self::Word;
+
+    fn store(&mut self, address: usize, value: Self::Word) -> Result<()>;
+
+    /// The next input, or `None` if we have to wait for it
+    fn read_input(&mut self) -> Result<Option<Self::Word>>;
+
+    fn write_output(&mut self, value: Self::Word);
+
+    /// Sees the value of every parameter the instruction reads, whether it was loaded or immediate
+    #[inline(always)]
+    fn observe_read(&mut self, _value: &Self::Word) {}
+
+    /// Runs an extension op code, returning where it jumps to if it does
+    fn extension(&mut self, instruction: &Instruction) -> Result<Option<usize>> {
+        Err(CPUError::InvalidOpCode {
+            ip: self.ip(),
+            word: instruction.op_code().code(),
+        })
+    }
+}
+
+/// Executes the instruction at our ip
+#[inline(always)]
+pub(super) fn step<M: Machine>(machine: &mut M) -> Result<ExecutionState> {
+    let ip = machine.ip();
+    let instruction = machine.decode()?;
+    let mut next = ip + instruction.size();
+
+    let state = match instruction.op_code() {
+        OpCode::Add | OpCode::Mul => {
+            let a = read(machine, &instruction, 0)?;
+            let b = read(machine, &instruction, 1)?;
+            let result = match instruction.op_code() {
+                OpCode::Add => a.add_with(&b, machine.arithmetic()),
+                _ => a.mul_with(&b, machine.arithmetic()),
+            };
+            let result = result.ok_or_else(|| CPUError::Overflow {
+                ip,
+                op_code: *instruction.op_code(),
+                operands: (a.saturate(), b.saturate()),
+            })?;
+            write(machine, &instruction, 2, result)?;
+            ExecutionState::Running
+        }
+        OpCode::Input => match machine.read_input()? {
+            Some(value) => {
+                write(machine, &instruction, 0, value)?;
+                ExecutionState::Running
+            }
+            None => ExecutionState::NeedsInput,
+        },
+        OpCode::Output => {
+            let value = read(machine, &instruction, 0)?;
+            machine.write_output(value);
+            ExecutionState::Running
+        }
+        OpCode::JumpIfTrue => {
+            if !read(machine, &instruction, 0)?.is_zero() {
+                next = address(ip, &read(machine, &instruction, 1)?)?;
+            }
+            ExecutionState::Running
+        }
+        OpCode::JumpIfFalse => {
+            if read(machine, &instruction, 0)?.is_zero() {
+                next = address(ip, &read(machine, &instruction, 1)?)?;
+            }
+            ExecutionState::Running
+        }
+        OpCode::LessThan => {
+            let less = read(machine, &instruction, 0)? < read(machine, &instruction, 1)?;
+            write(machine, &instruction, 2, M::Word::from_int(less as IntCode))?;
+            ExecutionState::Running
+        }
+        OpCode::Equals => {
+            let equal = read(machine, &instruction, 0)? == read(machine, &instruction, 1)?;
+            write(
+                machine,
+                &instruction,
+                2,
+                M::Word::from_int(equal as IntCode),
+            )?;
+            ExecutionState::Running
+        }
+        OpCode::AdjustBase => {
+            let adjustment = read(machine, &instruction, 0)?;
+            let relative_base = adjustment.offset(machine.relative_base()).ok_or_else(|| {
+                CPUError::RelativeBaseOverflow {
+                    ip,
+                    relative_base: machine.relative_base(),
+                    adjustment: adjustment.saturate(),
+                }
+            })?;
+            if relative_base.is_negative() {
+                return Err(CPUError::RelativeBaseUnderflow {
+                    ip,
+                    relative_base: relative_base.saturate(),
+                });
+            }
+            let relative_base = address(ip, &relative_base)?;
+            machine.set_relative_base(relative_base);
+            ExecutionState::Running
+        }
+        OpCode::Halt => ExecutionState::Halted,
+        OpCode::Extension { .. } => {
+            if let Some(address) = machine.extension(&instruction)? {
+                next = address;
+            }
+            ExecutionState::Running
+        }
+    };
+
+    if state == ExecutionState::Running {
+        machine.set_ip(next);
+    }
+
+    Ok(state)
+}
+
+/// Resolves a parameter of the instruction at our ip
+#[inline(always)]
+pub(super) fn parameter<M: Machine>(
+    machine: &M,
+    instruction: &Instruction,
+    offset: usize,
+) -> Result<Parameter<M::Word>> {
+    let ip = machine.ip();
+    let value = machine.fetch(ip + 1 + offset);
+    match instruction.modes[offset] {
+        Mode::Position => address(ip, &value).map(Parameter::Address),
+        Mode::Immediate => Ok(Parameter::Immediate(value)),
+        Mode::Relative => value
+            .offset(machine.relative_base())
+            .ok_or(CPUError::AddressTooLarge { ip })
+            .and_then(|address_value| address(ip, &address_value))
+            .map(Parameter::Address),
+    }
+}
+
+#[inline(always)]
+pub(super) fn read<M: Machine>(
+    machine: &mut M,
+    instruction: &Instruction,
+    offset: usize,
+) -> Result<M::Word> {
+    let value = match parameter(machine, instruction, offset)? {
+        Parameter::Address(address) => machine.load(address),
+        Parameter::Immediate(value) => value,
+    };
+    machine.observe_read(&value);
+
+    Ok(value)
+}
+
+#[inline(always)]
+pub(super) fn write<M: Machine>(
+    machine: &mut M,
+    instruction: &Instruction,
+    offset: usize,
+    value: M::Word,
+) -> Result<()> {
+    match parameter(machine, instruction, offset)? {
+        Parameter::Address(address) => machine.store(address, value),
+        Parameter::Immediate(_) => Err(CPUError::WriteToImmediate {
+            ip: machine.ip(),
+            parameter: offset,
+        }),
+    }
+}
+
+/// `value` as an address, for an instruction at `ip`
+#[inline(always)]
+pub(super) fn address<W: Word>(ip: usize, value: &W) -> Result<usize> {
+    if value.is_negative() {
+        Err(CPUError::NegativeAddress {
+            ip,
+            address: value.saturate(),
+        })
+    } else {
+        value.to_usize().ok_or(CPUError::AddressTooLarge { ip })
+    }
+}
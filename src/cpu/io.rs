@@ -0,0 +1,188 @@
+use crate::cpu::{CPUError, Execution, ExecutionState, IntCode, Result};
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Where an execution reads from. Returning `None` leaves the execution in
+/// `ExecutionState::NeedsInput` until a value shows up, and an error stops the execution
+pub trait Input {
+    fn read(&mut self) -> Result<Option<IntCode>>;
+}
+
+impl From<io::Error> for CPUError {
+    fn from(error: io::Error) -> Self {
+        CPUError::Io {
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Where an execution writes to
+pub trait Output {
+    fn write(&mut self, value: IntCode);
+}
+
+impl Input for VecDeque<IntCode> {
+    fn read(&mut self) -> Result<Option<IntCode>> {
+        Ok(self.pop_front())
+    }
+}
+
+impl Output for VecDeque<IntCode> {
+    fn write(&mut self, value: IntCode) {
+        self.push_back(value)
+    }
+}
+
+impl<F: FnMut() -> Option<IntCode>> Input for F {
+    fn read(&mut self) -> Result<Option<IntCode>> {
+        Ok(self())
+    }
+}
+
+impl<F: FnMut(IntCode)> Output for F {
+    fn write(&mut self, value: IntCode) {
+        self(value)
+    }
+}
+
+impl Input for Receiver<IntCode> {
+    fn read(&mut self) -> Result<Option<IntCode>> {
+        Ok(self.try_recv().ok())
+    }
+}
+
+impl Output for Sender<IntCode> {
+    fn write(&mut self, value: IntCode) {
+        // nobody is listening anymore, so there's nowhere for the value to go
+        let _ = self.send(value);
+    }
+}
+
+/// Reads stdin a line at a time, feeding it in as ascii with a trailing newline
+#[derive(Debug, Default)]
+pub struct AsciiStdin {
+    buffer: VecDeque<IntCode>,
+}
+
+impl AsciiStdin {
+    pub fn new() -> AsciiStdin {
+        AsciiStdin::default()
+    }
+
+    /// Feeds in `queued` before we start reading stdin
+    pub fn with_queued(queued: VecDeque<IntCode>) -> AsciiStdin {
+        AsciiStdin { buffer: queued }
+    }
+}
+
+impl Input for AsciiStdin {
+    fn read(&mut self) -> Result<Option<IntCode>> {
+        if self.buffer.is_empty() {
+            let mut line = String::new();
+            let read = io::stdin().lock().read_line(&mut line)?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            if !line.ends_with('\n') {
+                line.push('\n');
+            }
+            self.buffer.extend(line.bytes().map(IntCode::from));
+        }
+
+        Ok(self.buffer.pop_front())
+    }
+}
+
+/// Prints ascii output as text and anything outside of ascii as a number on its own line
+#[derive(Debug, Default)]
+pub struct AsciiStdout;
+
+impl Output for AsciiStdout {
+    fn write(&mut self, value: IntCode) {
+        let mut stdout = io::stdout();
+        if (0..128).contains(&value) {
+            write!(stdout, "{}", value as u8 as char)
+        } else {
+            writeln!(stdout, "{}", value)
+        }
+        .and_then(|_| stdout.flush())
+        .expect("Couldn't write to stdout");
+    }
+}
+
+/// Chains executions together: reading runs the upstream execution until it produces output. If
+/// the upstream execution fails, its error stops the downstream one too
+impl<I: Input> Input for Execution<I, VecDeque<IntCode>> {
+    fn read(&mut self) -> Result<Option<IntCode>> {
+        while self.output.is_empty() {
+            if self.step()? != ExecutionState::Running {
+                return Ok(None);
+            }
+        }
+
+        Ok(self.output.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::{parse_program, CPUError};
+    use std::sync::mpsc::channel;
+
+    // doubles every input until it reads a 0
+    static DOUBLER: &str = "3,15,1006,15,14,102,2,15,15,4,15,1105,1,0,99,0";
+
+    #[test]
+    fn closures_and_channels() {
+        let mut values = vec![0, 5, 4];
+        let mut outputs = vec![];
+        let mut execution = Execution::with_io(
+            parse_program(DOUBLER),
+            || values.pop(),
+            |value| outputs.push(value),
+        );
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        drop(execution);
+        assert_eq!(outputs, vec![8, 10]);
+
+        let (input, receiver) = channel();
+        let (sender, output) = channel();
+        let mut execution = Execution::with_io(parse_program(DOUBLER), receiver, sender);
+        assert_eq!(execution.run().unwrap(), ExecutionState::NeedsInput);
+
+        input.send(21).unwrap();
+        assert_eq!(execution.run().unwrap(), ExecutionState::NeedsInput);
+        assert_eq!(output.try_recv(), Ok(42));
+
+        input.send(0).unwrap();
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+    }
+
+    #[test]
+    fn chained_executions() {
+        let first = Execution::new_input(parse_program(DOUBLER), vec![1, 2, 3]);
+        let mut second = Execution::with_io(parse_program(DOUBLER), first, VecDeque::new());
+
+        // the first execution starves before it can send its 0
+        assert_eq!(second.run().unwrap(), ExecutionState::NeedsInput);
+        assert_eq!(Vec::from(second.output.clone()), vec![4, 8, 12]);
+
+        // once it halts there's nothing left to read
+        second.input.input.push_back(0);
+        assert_eq!(second.run().unwrap(), ExecutionState::NeedsInput);
+        assert_eq!(second.input.step().unwrap(), ExecutionState::Halted);
+
+        // an upstream fault comes out of the downstream execution
+        let broken = Execution::new(parse_program("104,1,42"));
+        let mut second = Execution::with_io(parse_program(DOUBLER), broken, VecDeque::new());
+        assert_eq!(
+            second.run(),
+            Err(CPUError::InvalidOpCode { ip: 2, word: 42 })
+        );
+        assert_eq!(Vec::from(second.output.clone()), vec![2]);
+    }
+}
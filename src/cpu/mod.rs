@@ -0,0 +1,1241 @@
+use crate::cpu::cache::{DecodedProgram, InstructionCache};
+use crate::cpu::coverage::Coverage;
+use crate::cpu::devices::Devices;
+use crate::cpu::extensions::{Extensions, Role};
+use crate::cpu::interpreter::Machine;
+use crate::cpu::io::{Input, Output};
+use crate::cpu::pages::{Pages, PAGE_SIZE};
+use crate::cpu::profile::Profile;
+use crate::cpu::trace::Trace;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+use std::result;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use wasm_bindgen::prelude::*;
+
+pub mod ascii;
+pub mod assembler;
+pub mod bignum;
+pub mod cache;
+pub mod cfg;
+pub mod coverage;
+pub mod debugger;
+pub mod devices;
+pub mod disassembler;
+pub mod extensions;
+mod fast;
+pub mod history;
+mod interpreter;
+pub mod io;
+pub mod pages;
+pub mod pipeline;
+pub mod profile;
+pub mod scheduler;
+pub mod snapshot;
+pub mod solver;
+pub mod trace;
+
+pub type IntCode = i64;
+pub type Memory = Vec<IntCode>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CPUError {
+    InvalidOpCode {
+        ip: usize,
+        word: IntCode,
+    },
+    InvalidMode {
+        ip: usize,
+        word: IntCode,
+        parameter: usize,
+    },
+    WriteToImmediate {
+        ip: usize,
+        parameter: usize,
+    },
+    NegativeAddress {
+        ip: usize,
+        address: IntCode,
+    },
+    RelativeBaseUnderflow {
+        ip: usize,
+        relative_base: IntCode,
+    },
+    RelativeBaseOverflow {
+        ip: usize,
+        relative_base: usize,
+        adjustment: IntCode,
+    },
+    MemoryLimit {
+        ip: usize,
+        address: usize,
+        limit: usize,
+    },
+    Overflow {
+        ip: usize,
+        op_code: OpCode,
+        operands: (IntCode, IntCode),
+    },
+    /// a handler for an extension op code failed
+    Extension {
+        ip: usize,
+        mnemonic: &'static str,
+        message: String,
+    },
+    /// an address past anything we can represent, like a relative address that overflows
+    AddressTooLarge {
+        ip: usize,
+    },
+    /// an input or output couldn't be read or written
+    Io {
+        message: String,
+    },
+}
+
+impl Display for CPUError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CPUError::InvalidOpCode { ip, word } => {
+                write!(f, "invalid op code in {} at ip {}", word, ip)
+            }
+            CPUError::InvalidMode {
+                ip,
+                word,
+                parameter,
+            } => write!(
+                f,
+                "invalid mode for parameter {} of {} at ip {}",
+                parameter + 1,
+                word,
+                ip
+            ),
+            CPUError::WriteToImmediate { ip, parameter } => write!(
+                f,
+                "parameter {} at ip {} writes in immediate mode",
+                parameter + 1,
+                ip
+            ),
+            CPUError::NegativeAddress { ip, address } => {
+                write!(f, "negative address {} at ip {}", address, ip)
+            }
+            CPUError::RelativeBaseUnderflow { ip, relative_base } => write!(
+                f,
+                "relative base adjusted to {} at ip {}",
+                relative_base, ip
+            ),
+            CPUError::RelativeBaseOverflow {
+                ip,
+                relative_base,
+                adjustment,
+            } => write!(
+                f,
+                "adjusting relative base {} by {} overflows at ip {}",
+                relative_base, adjustment, ip
+            ),
+            CPUError::MemoryLimit { ip, address, limit } => write!(
+                f,
+                "writing to {} at ip {} needs more than our limit of {} words",
+                address, ip, limit
+            ),
+            CPUError::Overflow {
+                ip,
+                op_code,
+                operands: (a, b),
+            } => write!(
+                f,
+                "{} of {} and {} overflows at ip {}",
+                op_code.mnemonic(),
+                a,
+                b,
+                ip
+            ),
+            CPUError::Extension {
+                ip,
+                mnemonic,
+                message,
+            } => write!(f, "{} at ip {} failed: {}", mnemonic, ip, message),
+            CPUError::AddressTooLarge { ip } => write!(f, "address at ip {} is too large", ip),
+            CPUError::Io { message } => write!(f, "io error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CPUError {}
+
+type Result<T> = result::Result<T, CPUError>;
+
+/// Errors decoding a single word, before we know where it lives in memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    InvalidOpCode { word: IntCode },
+    InvalidMode { word: IntCode, parameter: usize },
+}
+
+impl DecodeError {
+    fn at(self, ip: usize) -> CPUError {
+        match self {
+            DecodeError::InvalidOpCode { word } => CPUError::InvalidOpCode { ip, word },
+            DecodeError::InvalidMode { word, parameter } => CPUError::InvalidMode {
+                ip,
+                word,
+                parameter,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OpCode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+    /// registered with `Extensions`
+    Extension {
+        code: IntCode,
+        mnemonic: &'static str,
+        roles: &'static [Role],
+    },
+}
+
+impl OpCode {
+    #[inline(always)]
+    pub fn new(instruction: IntCode) -> result::Result<OpCode, DecodeError> {
+        let op_code = instruction % 100;
+
+        match op_code {
+            1 => Ok(OpCode::Add),
+            2 => Ok(OpCode::Mul),
+            3 => Ok(OpCode::Input),
+            4 => Ok(OpCode::Output),
+            5 => Ok(OpCode::JumpIfTrue),
+            6 => Ok(OpCode::JumpIfFalse),
+            7 => Ok(OpCode::LessThan),
+            8 => Ok(OpCode::Equals),
+            9 => Ok(OpCode::AdjustBase),
+            99 => Ok(OpCode::Halt),
+            _ => Err(DecodeError::InvalidOpCode { word: instruction }),
+        }
+    }
+
+    pub fn instruction_size(&self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => 4,
+            OpCode::Input | OpCode::Output | OpCode::AdjustBase => 2,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 3,
+            OpCode::Halt => 0,
+            OpCode::Extension { roles, .. } => roles.len() + 1,
+        }
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        match self {
+            OpCode::Halt => 0,
+            _ => self.instruction_size() - 1,
+        }
+    }
+
+    pub fn code(&self) -> IntCode {
+        match self {
+            OpCode::Add => 1,
+            OpCode::Mul => 2,
+            OpCode::Input => 3,
+            OpCode::Output => 4,
+            OpCode::JumpIfTrue => 5,
+            OpCode::JumpIfFalse => 6,
+            OpCode::LessThan => 7,
+            OpCode::Equals => 8,
+            OpCode::AdjustBase => 9,
+            OpCode::Halt => 99,
+            OpCode::Extension { code, .. } => *code,
+        }
+    }
+
+    /// How this op code uses each of its parameters
+    pub fn roles(&self) -> &'static [Role] {
+        match self {
+            OpCode::Add | OpCode::Mul | OpCode::LessThan | OpCode::Equals => {
+                &[Role::Read, Role::Read, Role::Write]
+            }
+            OpCode::Input => &[Role::Write],
+            OpCode::Output | OpCode::AdjustBase => &[Role::Read],
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => &[Role::Read, Role::Read],
+            OpCode::Halt => &[],
+            OpCode::Extension { roles, .. } => roles,
+        }
+    }
+
+    /// The indexes of every parameter this op code writes to
+    pub fn write_parameters(&self) -> impl Iterator<Item = usize> {
+        self.roles()
+            .iter()
+            .enumerate()
+            .filter(|(_, role)| **role == Role::Write)
+            .map(|(i, _)| i)
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            OpCode::Add => "ADD",
+            OpCode::Mul => "MUL",
+            OpCode::Input => "IN",
+            OpCode::Output => "OUT",
+            OpCode::JumpIfTrue => "JT",
+            OpCode::JumpIfFalse => "JF",
+            OpCode::LessThan => "LT",
+            OpCode::Equals => "EQ",
+            OpCode::AdjustBase => "ARB",
+            OpCode::Halt => "HLT",
+            OpCode::Extension { mnemonic, .. } => mnemonic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Instruction {
+    op_code: OpCode,
+    modes: [Mode; 3],
+}
+
+impl Instruction {
+    pub fn new(instruction: IntCode) -> result::Result<Instruction, DecodeError> {
+        Instruction::with_op_code(instruction, OpCode::new(instruction)?)
+    }
+
+    /// Decodes `instruction`, which can also use any op code in `extensions`
+    #[inline(always)]
+    pub fn with_extensions(
+        instruction: IntCode,
+        extensions: &Extensions,
+    ) -> result::Result<Instruction, DecodeError> {
+        Instruction::with_op_code(instruction, extensions.op_code(instruction)?)
+    }
+
+    #[inline(always)]
+    fn with_op_code(
+        instruction: IntCode,
+        op_code: OpCode,
+    ) -> result::Result<Instruction, DecodeError> {
+        let mode = |parameter: usize| {
+            let digit = (instruction / IntCode::pow(10, parameter as u32 + 2)) % 10;
+            Mode::new(digit).ok_or(DecodeError::InvalidMode {
+                word: instruction,
+                parameter,
+            })
+        };
+
+        Ok(Instruction {
+            op_code,
+            modes: [mode(0)?, mode(1)?, mode(2)?],
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.op_code.instruction_size()
+    }
+
+    pub fn op_code(&self) -> &OpCode {
+        &self.op_code
+    }
+
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes[..self.op_code.parameter_count()]
+    }
+
+    /// The canonical word for this instruction, ignoring the modes of unused parameters
+    pub fn encode(&self) -> IntCode {
+        self.modes()
+            .iter()
+            .enumerate()
+            .fold(self.op_code.code(), |word, (i, mode)| {
+                word + mode.code() * IntCode::pow(10, i as u32 + 2)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    #[inline(always)]
+    pub fn new(int_code: IntCode) -> Option<Mode> {
+        match int_code {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> IntCode {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// A parameter resolved against the current state of an execution
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Parameter<W = IntCode> {
+    Address(usize),
+    Immediate(W),
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionState {
+    Running,
+    Halted,
+    NeedsInput,
+    /// we stopped early so a runaway program can't hang us, running again picks up where we left off
+    BudgetExhausted,
+}
+
+/// What `ADD` and `MUL` do when the result doesn't fit in an `IntCode`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Arithmetic {
+    #[default]
+    Wrapping,
+    /// fail with `CPUError::Overflow`
+    Checked,
+    Saturating,
+}
+
+/// writes below this grow `memory`, anything higher goes in to `pages`
+pub const CONTIGUOUS_LIMIT: usize = 1 << 16;
+
+#[cfg(not(target_arch = "wasm32"))]
+const DEADLINE_CHECK_INTERVAL: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct Execution<I = VecDeque<IntCode>, O = VecDeque<IntCode>> {
+    pub ip: usize,
+    pub relative_base: usize,
+    /// the program and anything written just past it
+    pub memory: Memory,
+    /// everything written far past the end of `memory`
+    pub pages: Pages,
+    /// how many words `memory` and `pages` may use between them
+    pub memory_limit: Option<usize>,
+    pub arithmetic: Arithmetic,
+    pub extensions: Extensions,
+    pub devices: Devices,
+    pub input: I,
+    pub output: O,
+    pub trace: Option<Trace>,
+    pub profile: Option<Profile>,
+    pub coverage: Option<Coverage>,
+    pub cache: Option<InstructionCache>,
+}
+
+impl Execution {
+    pub fn new(memory: Memory) -> Execution {
+        Self::new_input(memory, vec![])
+    }
+
+    pub fn new_input(memory: Memory, input: Memory) -> Execution {
+        Execution::with_io(memory, input.into(), VecDeque::new())
+    }
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    pub fn with_io(memory: Memory, input: I, output: O) -> Execution<I, O> {
+        Execution {
+            ip: 0,
+            relative_base: 0,
+            memory,
+            pages: Pages::new(),
+            memory_limit: None,
+            arithmetic: Arithmetic::default(),
+            extensions: Extensions::new(),
+            devices: Devices::new(),
+            input,
+            output,
+            trace: None,
+            profile: None,
+            coverage: None,
+            cache: None,
+        }
+    }
+
+    /// Decodes our memory up front so loops don't decode it again. To run the same program many
+    /// times, decode it once with `DecodedProgram` and make the executions from that instead.
+    /// It's not on by default, since decoding all of memory only pays off once the program loops
+    pub fn enable_instruction_cache(&mut self) {
+        let program = DecodedProgram::with_extensions(self.memory.clone(), self.extensions.clone());
+        self.cache = Some(InstructionCache::new(program));
+    }
+
+    pub fn run(&mut self) -> Result<ExecutionState> {
+        if self.is_plain() {
+            return if self.cache.is_some() {
+                self.run_fast::<true>(usize::MAX)
+            } else {
+                self.run_fast::<false>(usize::MAX)
+            };
+        }
+
+        let mut state = self.step()?;
+        while state == ExecutionState::Running {
+            state = self.step()?;
+        }
+
+        Ok(state)
+    }
+
+    /// Runs at most `budget` instructions
+    pub fn run_for(&mut self, budget: usize) -> Result<ExecutionState> {
+        if self.is_plain() {
+            return if self.cache.is_some() {
+                self.run_fast::<true>(budget)
+            } else {
+                self.run_fast::<false>(budget)
+            };
+        }
+
+        for _ in 0..budget {
+            let state = self.step()?;
+            if state != ExecutionState::Running {
+                return Ok(state);
+            }
+        }
+
+        Ok(ExecutionState::BudgetExhausted)
+    }
+
+    /// Runs until `deadline`, only checking the clock every so often. There is no clock on wasm
+    /// so use `run_for` there
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_until_deadline(&mut self, deadline: Instant) -> Result<ExecutionState> {
+        while Instant::now() < deadline {
+            let state = self.run_for(DEADLINE_CHECK_INTERVAL)?;
+            if state != ExecutionState::BudgetExhausted {
+                return Ok(state);
+            }
+        }
+
+        Ok(ExecutionState::BudgetExhausted)
+    }
+
+    pub fn step(&mut self) -> Result<ExecutionState> {
+        if !self.is_plain() {
+            self.step_instrumented()
+        } else if self.cache.is_some() {
+            interpreter::step(&mut Plain::<_, _, true>(self))
+        } else {
+            interpreter::step(&mut Plain::<_, _, false>(self))
+        }
+    }
+
+    /// Whether there's nothing attached that the interpreter needs to check on every instruction,
+    /// other than the cache
+    #[inline(always)]
+    fn is_plain(&self) -> bool {
+        self.trace.is_none()
+            && self.profile.is_none()
+            && self.coverage.is_none()
+            && self.devices.is_empty()
+    }
+
+    /// Steps with devices, or keeping track of what we executed for the trace, profile and coverage
+    #[inline(never)]
+    fn step_instrumented(&mut self) -> Result<ExecutionState> {
+        if self.trace.is_none() && self.profile.is_none() && self.coverage.is_none() {
+            return interpreter::step(self);
+        }
+
+        let ip = self.ip;
+        let instruction = self.instruction()?;
+        let mut observed = Observed {
+            execution: self,
+            reads: vec![],
+            writes: vec![],
+        };
+        let state = interpreter::step(&mut observed)?;
+        let Observed { reads, writes, .. } = observed;
+
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(ip, &instruction, &state);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(ip, &instruction, &state, &reads);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.record(ip, &instruction, &state, reads, writes);
+        }
+
+        Ok(state)
+    }
+
+    /// Decodes the instruction at our ip without executing it
+    #[inline(always)]
+    pub fn instruction(&self) -> Result<Instruction> {
+        Instruction::with_extensions(self[self.ip], &self.extensions).map_err(|e| e.at(self.ip))
+    }
+
+    #[inline(always)]
+    fn cached_instruction(&mut self) -> Result<Instruction> {
+        let (ip, word) = (self.ip, self[self.ip]);
+        match self.cache.as_mut().and_then(|cache| cache.get(ip, word)) {
+            Some(instruction) => Ok(instruction),
+            None => self.instruction(),
+        }
+    }
+
+    pub fn parameters(&self, instruction: &Instruction) -> Result<Vec<Parameter>> {
+        (0..instruction.op_code.parameter_count())
+            .map(|i| interpreter::parameter(self, instruction, i))
+            .collect()
+    }
+}
+
+/// An execution with nothing attached, so the interpreter can skip checking for devices and
+/// instrumentation on every instruction. `CACHED` is whether it has a cache, so that's only checked
+/// once per run
+struct Plain<'a, I, O, const CACHED: bool>(&'a mut Execution<I, O>);
+
+impl<I: Input, O: Output, const CACHED: bool> Machine for Plain<'_, I, O, CACHED> {
+    type Word = IntCode;
+
+    #[inline(always)]
+    fn ip(&self) -> usize {
+        self.0.ip
+    }
+
+    #[inline(always)]
+    fn set_ip(&mut self, ip: usize) {
+        self.0.ip = ip;
+    }
+
+    #[inline(always)]
+    fn relative_base(&self) -> usize {
+        self.0.relative_base
+    }
+
+    #[inline(always)]
+    fn set_relative_base(&mut self, relative_base: usize) {
+        self.0.relative_base = relative_base;
+    }
+
+    #[inline(always)]
+    fn arithmetic(&self) -> Arithmetic {
+        self.0.arithmetic
+    }
+
+    #[inline(always)]
+    fn decode(&mut self) -> Result<Instruction> {
+        if CACHED {
+            self.0.cached_instruction()
+        } else {
+            self.0.instruction()
+        }
+    }
+
+    #[inline(always)]
+    fn fetch(&self, address: usize) -> IntCode {
+        self.0[address]
+    }
+
+    #[inline(always)]
+    fn load(&self, address: usize) -> IntCode {
+        self.0[address]
+    }
+
+    #[inline(always)]
+    fn store(&mut self, address: usize, value: IntCode) -> Result<()> {
+        *self.0.cell_mut(address)? = value;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn read_input(&mut self) -> Result<Option<IntCode>> {
+        self.0.input.read()
+    }
+
+    #[inline(always)]
+    fn write_output(&mut self, value: IntCode) {
+        self.0.output.write(value);
+    }
+
+    fn extension(&mut self, instruction: &Instruction) -> Result<Option<usize>> {
+        self.0
+            .registered_extension(instruction)?
+            .call(self, instruction)
+    }
+}
+
+impl<I: Input, O: Output> Machine for Execution<I, O> {
+    type Word = IntCode;
+
+    #[inline]
+    fn ip(&self) -> usize {
+        self.ip
+    }
+
+    #[inline]
+    fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+    }
+
+    #[inline]
+    fn relative_base(&self) -> usize {
+        self.relative_base
+    }
+
+    #[inline]
+    fn set_relative_base(&mut self, relative_base: usize) {
+        self.relative_base = relative_base;
+    }
+
+    #[inline]
+    fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    #[inline]
+    fn decode(&mut self) -> Result<Instruction> {
+        if self.cache.is_none() {
+            self.instruction()
+        } else {
+            self.cached_instruction()
+        }
+    }
+
+    #[inline]
+    fn fetch(&self, address: usize) -> IntCode {
+        self[address]
+    }
+
+    #[inline]
+    fn load(&self, address: usize) -> IntCode {
+        if self.devices.is_empty() {
+            self[address]
+        } else {
+            self.read_device(address)
+        }
+    }
+
+    #[inline]
+    fn store(&mut self, address: usize, value: IntCode) -> Result<()> {
+        if self.devices.is_empty() {
+            *self.cell_mut(address)? = value;
+            Ok(())
+        } else {
+            self.write_device(address, value)
+        }
+    }
+
+    #[inline]
+    fn read_input(&mut self) -> Result<Option<IntCode>> {
+        self.input.read()
+    }
+
+    #[inline]
+    fn write_output(&mut self, value: IntCode) {
+        self.output.write(value);
+    }
+
+    fn extension(&mut self, instruction: &Instruction) -> Result<Option<usize>> {
+        self.registered_extension(instruction)?
+            .call(self, instruction)
+    }
+}
+
+/// An execution that keeps the values its instruction reads and writes, as it reads and writes
+/// them, so devices show up as what they really returned
+struct Observed<'a, I, O> {
+    execution: &'a mut Execution<I, O>,
+    reads: Vec<IntCode>,
+    writes: Vec<(usize, IntCode)>,
+}
+
+impl<I: Input, O: Output> Machine for Observed<'_, I, O> {
+    type Word = IntCode;
+
+    fn ip(&self) -> usize {
+        self.execution.ip
+    }
+
+    fn set_ip(&mut self, ip: usize) {
+        self.execution.ip = ip;
+    }
+
+    fn relative_base(&self) -> usize {
+        self.execution.relative_base
+    }
+
+    fn set_relative_base(&mut self, relative_base: usize) {
+        self.execution.relative_base = relative_base;
+    }
+
+    fn arithmetic(&self) -> Arithmetic {
+        self.execution.arithmetic
+    }
+
+    fn decode(&mut self) -> Result<Instruction> {
+        self.execution.decode()
+    }
+
+    fn fetch(&self, address: usize) -> IntCode {
+        self.execution.fetch(address)
+    }
+
+    fn load(&self, address: usize) -> IntCode {
+        self.execution.load(address)
+    }
+
+    fn store(&mut self, address: usize, value: IntCode) -> Result<()> {
+        self.execution.store(address, value)?;
+        self.writes.push((address, value));
+        Ok(())
+    }
+
+    fn read_input(&mut self) -> Result<Option<IntCode>> {
+        self.execution.read_input()
+    }
+
+    fn write_output(&mut self, value: IntCode) {
+        self.execution.write_output(value);
+    }
+
+    fn observe_read(&mut self, value: &IntCode) {
+        self.reads.push(*value);
+    }
+
+    fn extension(&mut self, instruction: &Instruction) -> Result<Option<usize>> {
+        self.execution
+            .registered_extension(instruction)?
+            .call(self, instruction)
+    }
+}
+
+impl<I, O> Execution<I, O> {
+    /// The cell for `address`, growing memory if we have to
+    #[inline(always)]
+    pub fn cell_mut(&mut self, address: usize) -> Result<&mut IntCode> {
+        if address < self.memory.len() {
+            Ok(&mut self.memory[address])
+        } else {
+            self.grow_cell(address)
+        }
+    }
+
+    /// A cell past the end of `memory`, growing it or allocating a page
+    #[inline(never)]
+    fn grow_cell(&mut self, address: usize) -> Result<&mut IntCode> {
+        let contiguous = address < CONTIGUOUS_LIMIT;
+        let growth = if contiguous {
+            address + 1 - self.memory.len()
+        } else if self.pages.is_allocated(address) {
+            0
+        } else {
+            PAGE_SIZE
+        };
+
+        match self.memory_limit {
+            Some(limit) if self.memory.len() + self.pages.words() + growth > limit => {
+                Err(CPUError::MemoryLimit {
+                    ip: self.ip,
+                    address,
+                    limit,
+                })
+            }
+            _ if contiguous => {
+                self.memory.resize(address + 1, 0);
+                Ok(&mut self.memory[address])
+            }
+            _ => Ok(self.pages.get_mut(address)),
+        }
+    }
+}
+
+impl<I> Execution<I, VecDeque<IntCode>> {
+    pub fn expect_pop(&mut self) -> IntCode {
+        self.output
+            .pop_front()
+            .expect("Expected an output from our program")
+    }
+}
+
+impl<I: Input> Execution<I, VecDeque<IntCode>> {
+    /// Runs until `predicate` holds, we halt or we need input, draining everything we output.
+    /// The predicate is checked before each instruction so we stop before the instruction it
+    /// matched on runs
+    pub fn run_until<P>(&mut self, mut predicate: P) -> Result<(Vec<IntCode>, ExecutionState)>
+    where
+        P: FnMut(&Self) -> bool,
+    {
+        let mut state = ExecutionState::Running;
+        while state == ExecutionState::Running && !predicate(self) {
+            state = self.step()?;
+        }
+
+        Ok((self.output.drain(..).collect(), state))
+    }
+
+    /// Runs until we have `count` outputs, we halt or we need input. Only `count` outputs are
+    /// drained, anything extra that was already queued is left for next time
+    pub fn run_until_outputs(&mut self, count: usize) -> Result<(Vec<IntCode>, ExecutionState)> {
+        let mut state = ExecutionState::Running;
+        while state == ExecutionState::Running && self.output.len() < count {
+            state = self.step()?;
+        }
+
+        let available = self.output.len().min(count);
+        Ok((self.output.drain(..available).collect(), state))
+    }
+
+    /// Runs until we're about to execute the instruction at `ip`, we halt or we need input
+    pub fn run_to_ip(&mut self, ip: usize) -> Result<(Vec<IntCode>, ExecutionState)> {
+        self.run_until(|execution| execution.ip == ip)
+    }
+}
+
+impl<I, O> Index<usize> for Execution<I, O> {
+    type Output = IntCode;
+
+    fn index(&self, address: usize) -> &Self::Output {
+        match self.memory.get(address) {
+            Some(value) => value,
+            // memory is initialized to zero
+            None => self.pages.get(address).unwrap_or(&0),
+        }
+    }
+}
+
+/// Panics when writing past our memory limit, use `cell_mut` to handle that instead
+impl<I, O> IndexMut<usize> for Execution<I, O> {
+    fn index_mut(&mut self, address: usize) -> &mut Self::Output {
+        self.cell_mut(address)
+            .unwrap_or_else(|e| panic!("CPU Error: {}", e))
+    }
+}
+
+impl From<Memory> for Execution {
+    fn from(memory: Memory) -> Self {
+        Execution::new(memory)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// the index of the token, which is also the address it would have been loaded at
+    pub index: usize,
+    pub token: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "token {} ({:?}) couldn't be parsed as IntCode",
+            self.index, self.token
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// ignore everything from a `#` to the end of the line
+    pub comments: bool,
+}
+
+pub fn parse_program(raw_memory: &str) -> Memory {
+    try_parse_program(raw_memory).unwrap_or_else(|e| panic!("Parse Error: {}", e))
+}
+
+pub fn try_parse_program(raw_memory: &str) -> result::Result<Memory, ParseError> {
+    parse_program_with(raw_memory, &ParseOptions::default())
+}
+
+/// Tokens are separated by commas or newlines, blank tokens (like from a trailing comma) are skipped
+pub fn parse_program_with(
+    raw_memory: &str,
+    options: &ParseOptions,
+) -> result::Result<Memory, ParseError> {
+    tokens(raw_memory, options)
+        .map(|(index, token)| {
+            token.parse::<IntCode>().map_err(|_| ParseError {
+                index,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn tokens<'a>(
+    raw_memory: &'a str,
+    options: &'a ParseOptions,
+) -> impl Iterator<Item = (usize, &'a str)> {
+    raw_memory
+        .lines()
+        .map(move |line| match line.find('#') {
+            Some(comment) if options.comments => &line[..comment],
+            _ => line,
+        })
+        .flat_map(|line| line.split(','))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+        .enumerate()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_instruction() {
+        assert_eq!(
+            Instruction {
+                op_code: OpCode::Mul,
+                modes: [Mode::Position, Mode::Immediate, Mode::Position],
+            },
+            Instruction::new(1002).unwrap()
+        );
+
+        assert_eq!(
+            Instruction {
+                op_code: OpCode::Mul,
+                modes: [Mode::Position, Mode::Immediate, Mode::Immediate],
+            },
+            Instruction::new(11002).unwrap()
+        );
+    }
+
+    #[test]
+    fn cpu_position_mode() {
+        assert_eq!(run("3,9,8,9,10,9,4,9,99,-1,8", vec![7]), vec![0]);
+        assert_eq!(run("3,9,8,9,10,9,4,9,99,-1,8", vec![8]), vec![1]);
+    }
+
+    #[test]
+    fn cpu_relative_mode() {
+        assert_eq!(
+            run(
+                "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+                vec![],
+            ),
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+        );
+        assert_eq!(
+            run("1102,34915192,34915192,7,4,7,99,0", vec![]),
+            vec![1219070632396864]
+        );
+        assert_eq!(
+            run("104,1125899906842624,99", vec![]),
+            vec![1125899906842624]
+        );
+    }
+
+    #[test]
+    fn cpu_errors() {
+        let error = |program: &str| Execution::new(parse_program(program)).run().unwrap_err();
+
+        assert_eq!(
+            error("1,0,0,0,42"),
+            CPUError::InvalidOpCode { ip: 4, word: 42 }
+        );
+        assert_eq!(
+            error("1,0,0,0,301,0,0,0"),
+            CPUError::InvalidMode {
+                ip: 4,
+                word: 301,
+                parameter: 0
+            }
+        );
+        assert_eq!(
+            error("11101,1,1,1,99"),
+            CPUError::WriteToImmediate {
+                ip: 0,
+                parameter: 2
+            }
+        );
+        assert_eq!(
+            error("4,-3,99"),
+            CPUError::NegativeAddress { ip: 0, address: -3 }
+        );
+        assert_eq!(
+            error("1105,1,-1"),
+            CPUError::NegativeAddress { ip: 0, address: -1 }
+        );
+        assert_eq!(
+            error("109,2,109,-5,99"),
+            CPUError::RelativeBaseUnderflow {
+                ip: 2,
+                relative_base: -3
+            }
+        );
+        assert_eq!(
+            error("109,1,204,9223372036854775807,99"),
+            CPUError::AddressTooLarge { ip: 2 }
+        );
+        assert_eq!(
+            error("109,9223372036854775807,109,1,99"),
+            CPUError::RelativeBaseOverflow {
+                ip: 2,
+                relative_base: 9223372036854775807,
+                adjustment: 1
+            }
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let run = |program: &str, arithmetic| {
+            let mut execution = Execution::new(parse_program(program));
+            execution.arithmetic = arithmetic;
+            execution.run().map(|_| execution[0])
+        };
+        let add = "1101,9223372036854775807,1,0,99";
+        let mul = "1102,-4611686018427387905,2,0,99";
+
+        assert_eq!(run(add, Arithmetic::Wrapping), Ok(IntCode::MIN));
+        assert_eq!(run(mul, Arithmetic::Wrapping), Ok(IntCode::MAX - 1));
+        assert_eq!(run(add, Arithmetic::Saturating), Ok(IntCode::MAX));
+        assert_eq!(run(mul, Arithmetic::Saturating), Ok(IntCode::MIN));
+
+        let error = run(mul, Arithmetic::Checked).unwrap_err();
+        assert_eq!(
+            error,
+            CPUError::Overflow {
+                ip: 0,
+                op_code: OpCode::Mul,
+                operands: (-4611686018427387905, 2)
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "MUL of -4611686018427387905 and 2 overflows at ip 0"
+        );
+    }
+
+    #[test]
+    fn sparse_memory() {
+        // write to a billion and read it back
+        let program = "1101,20,22,1000000000,4,1000000000,4,999999999,99";
+
+        let mut execution = Execution::new(parse_program(program));
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+        assert_eq!(Vec::from(execution.output.clone()), vec![42, 0]);
+        assert_eq!(execution.memory.len(), 9);
+        assert_eq!(execution.pages.words(), PAGE_SIZE);
+        assert_eq!(execution.pages.cells(), vec![(1_000_000_000, 42)]);
+        assert_eq!(execution.memory, parse_program(program));
+
+        let mut limited = Execution::new(parse_program(program));
+        limited.memory_limit = Some(1000);
+        assert_eq!(
+            limited.run().unwrap_err(),
+            CPUError::MemoryLimit {
+                ip: 0,
+                address: 1_000_000_000,
+                limit: 1000
+            }
+        );
+
+        // low addresses still grow memory
+        let mut execution = Execution::new(parse_program("1101,1,1,500,99"));
+        execution.run().unwrap();
+        assert_eq!(execution.memory.len(), 501);
+        assert_eq!(execution.pages.words(), 0);
+    }
+
+    #[test]
+    fn budgets() {
+        // loops forever
+        let mut execution = Execution::new(parse_program("1105,1,0"));
+        assert_eq!(
+            execution.run_for(100).unwrap(),
+            ExecutionState::BudgetExhausted
+        );
+        assert_eq!(
+            execution
+                .run_until_deadline(Instant::now() + Duration::from_millis(10))
+                .unwrap(),
+            ExecutionState::BudgetExhausted
+        );
+
+        let mut execution =
+            Execution::new_input(parse_program("3,9,8,9,10,9,4,9,99,-1,8"), vec![8]);
+        assert_eq!(
+            execution.run_for(2).unwrap(),
+            ExecutionState::BudgetExhausted
+        );
+        assert!(execution.output.is_empty());
+        assert_eq!(execution.run_for(100).unwrap(), ExecutionState::Halted);
+        assert_eq!(execution.expect_pop(), 1);
+    }
+
+    #[test]
+    fn run_until() {
+        // output our input, then count up forever
+        let program = "3,100,4,100,104,7,1001,101,1,101,4,101,1105,1,6";
+
+        let mut execution = Execution::new_input(parse_program(program), vec![42]);
+        assert_eq!(
+            execution.run_until_outputs(3).unwrap(),
+            (vec![42, 7, 1], ExecutionState::Running)
+        );
+        assert_eq!(
+            execution.run_until(|e| e[101] == 3).unwrap(),
+            (vec![2], ExecutionState::Running)
+        );
+        assert_eq!(
+            execution.run_to_ip(12).unwrap(),
+            (vec![3], ExecutionState::Running)
+        );
+        assert_eq!(execution.ip, 12);
+
+        let mut execution = Execution::new(parse_program(program));
+        assert_eq!(
+            execution.run_until_outputs(2).unwrap(),
+            (vec![], ExecutionState::NeedsInput)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(try_parse_program("1,2,\n3,\n\n"), Ok(vec![1, 2, 3]));
+        assert_eq!(try_parse_program(" \n"), Ok(vec![]));
+        assert_eq!(
+            try_parse_program("1, 2, x3"),
+            Err(ParseError {
+                index: 2,
+                token: "x3".to_string()
+            })
+        );
+
+        let commented = "# header\n1,2,3 # add\n99";
+        assert!(try_parse_program(commented).is_err());
+        assert_eq!(
+            parse_program_with(commented, &ParseOptions { comments: true }),
+            Ok(vec![1, 2, 3, 99])
+        );
+    }
+
+    fn run(program: &str, input: Memory) -> Vec<IntCode> {
+        let mut execution: Execution = Execution::new_input(parse_program(program), input);
+
+        execution.run().expect("This should always work");
+
+        execution.output.into()
+    }
+}
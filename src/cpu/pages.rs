@@ -0,0 +1,67 @@
+use crate::cpu::IntCode;
+use std::collections::HashMap;
+
+pub const PAGE_SIZE: usize = 1024;
+
+/// Memory far past the end of a program, only allocated a page at a time as it's written to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pages {
+    pages: HashMap<usize, Box<[IntCode]>>,
+}
+
+impl Pages {
+    pub fn new() -> Pages {
+        Pages::default()
+    }
+
+    pub fn get(&self, address: usize) -> Option<&IntCode> {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map(|page| &page[address % PAGE_SIZE])
+    }
+
+    /// Finds the cell for `address`, allocating its page if we haven't seen it yet
+    pub fn get_mut(&mut self, address: usize) -> &mut IntCode {
+        let page = self
+            .pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| vec![0; PAGE_SIZE].into_boxed_slice());
+
+        &mut page[address % PAGE_SIZE]
+    }
+
+    /// Drops the page holding `address`, like undoing the write that allocated it
+    pub fn free(&mut self, address: usize) {
+        self.pages.remove(&(address / PAGE_SIZE));
+    }
+
+    pub fn is_allocated(&self, address: usize) -> bool {
+        self.pages.contains_key(&(address / PAGE_SIZE))
+    }
+
+    /// How many words we've allocated across all of our pages
+    pub fn words(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+
+    /// Every non-zero cell, sorted by address
+    pub fn cells(&self) -> Vec<(usize, IntCode)> {
+        let mut cells: Vec<(usize, IntCode)> = self
+            .pages
+            .iter()
+            .flat_map(|(index, page)| {
+                page.iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value != 0)
+                    .map(move |(offset, value)| (index * PAGE_SIZE + offset, *value))
+            })
+            .collect();
+        cells.sort_by_key(|(address, _)| *address);
+
+        cells
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+}
@@ -0,0 +1,202 @@
+use crate::cpu::scheduler::{Link, LinkError, MachineError, MachineId, Scheduler, SchedulerState};
+use crate::cpu::{Execution, IntCode, Memory};
+use permutohedron::LexicalPermutation;
+
+pub type StageId = MachineId;
+
+/// A network of programs wired output to input, like day 7's amplifiers. Stages can fan out to
+/// several others, take input from several others and loop back round
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    /// every stage and edge, which we run a copy of
+    scheduler: Scheduler,
+    output: Option<StageId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineResult {
+    /// the last value the output stage sent
+    pub signal: Option<IntCode>,
+    /// everything each stage output, in the order they were added
+    pub history: Vec<Vec<IntCode>>,
+    pub state: SchedulerState,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Stages that all run `program`, each getting its phase as its first input and the first also
+    /// getting `signal`, with each passing its output on to the next
+    pub fn chain(program: &Memory, phases: &[IntCode], signal: IntCode) -> Pipeline {
+        let mut pipeline = Pipeline::new();
+        let stages: Vec<StageId> = phases
+            .iter()
+            .enumerate()
+            .map(|(i, &phase)| {
+                let input = if i == 0 {
+                    vec![phase, signal]
+                } else {
+                    vec![phase]
+                };
+                pipeline.stage(program.clone(), input)
+            })
+            .collect();
+        for pair in stages.windows(2) {
+            pipeline
+                .edge(pair[0], pair[1])
+                .expect("We just added both stages");
+        }
+
+        pipeline
+    }
+
+    /// A chain whose last stage feeds back in to the first
+    pub fn ring(program: &Memory, phases: &[IntCode], signal: IntCode) -> Pipeline {
+        let mut pipeline = Pipeline::chain(program, phases, signal);
+        if !phases.is_empty() {
+            pipeline
+                .edge(phases.len() - 1, 0)
+                .expect("The chain has a stage for every phase");
+        }
+
+        pipeline
+    }
+
+    /// Adds a stage that starts with `input` queued, e.g. its phase setting
+    pub fn stage(&mut self, program: Memory, input: Vec<IntCode>) -> StageId {
+        let stage = self.scheduler.add(Execution::new_input(program, input));
+        self.scheduler
+            .record_history(stage)
+            .expect("We just added the stage");
+
+        stage
+    }
+
+    /// Sends everything `from` outputs to `to`, after anything already queued for it
+    pub fn edge(&mut self, from: StageId, to: StageId) -> Result<&mut Pipeline, LinkError> {
+        self.scheduler.link(from, Link::Direct(to))?;
+        Ok(self)
+    }
+
+    /// Which stage's output is the signal, the last stage by default
+    pub fn output(&mut self, stage: StageId) -> Result<&mut Pipeline, LinkError> {
+        if stage >= self.scheduler.len() {
+            return Err(LinkError::UnknownMachine(stage));
+        }
+        self.output = Some(stage);
+
+        Ok(self)
+    }
+
+    /// Runs every stage until they all halt or are stuck waiting for input
+    pub fn run(&self) -> Result<PipelineResult, MachineError> {
+        let mut scheduler = self.scheduler.clone();
+        let state = scheduler.run()?;
+
+        let history: Vec<Vec<IntCode>> = (0..scheduler.len())
+            .map(|id| {
+                scheduler
+                    .machine(id)
+                    .and_then(|machine| machine.history())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let signal = self
+            .output
+            .or_else(|| history.len().checked_sub(1))
+            .and_then(|stage| history.get(stage))
+            .and_then(|outputs| outputs.last().cloned());
+
+        Ok(PipelineResult {
+            signal,
+            history,
+            state,
+        })
+    }
+}
+
+/// Tries every ordering of `phases`, returning the one that gives the highest signal along with
+/// that signal
+pub fn search_phases<F>(
+    phases: &[IntCode],
+    build: F,
+) -> Result<Option<(Vec<IntCode>, IntCode)>, MachineError>
+where
+    F: Fn(&[IntCode]) -> Pipeline,
+{
+    let mut phases = phases.to_vec();
+    phases.sort_unstable();
+
+    let mut best: Option<(Vec<IntCode>, IntCode)> = None;
+    loop {
+        if let Some(signal) = build(&phases).run()?.signal {
+            if best.as_ref().is_none_or(|(_, max)| signal > *max) {
+                best = Some((phases.clone(), signal));
+            }
+        }
+
+        if !phases.next_permutation() {
+            return Ok(best);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn amplifiers() {
+        // the day 7 examples
+        let program = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let chain = |phases: &[IntCode]| Pipeline::chain(&program, phases, 0);
+        assert_eq!(
+            search_phases(&[0, 1, 2, 3, 4], chain),
+            Ok(Some((vec![4, 3, 2, 1, 0], 43210)))
+        );
+
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        );
+        let result = Pipeline::ring(&program, &[9, 8, 7, 6, 5], 0).run().unwrap();
+        assert_eq!(result.signal, Some(139629729));
+        assert_eq!(result.state, SchedulerState::AllHalted);
+        assert_eq!(result.history[4].len(), 5);
+    }
+
+    #[test]
+    fn fan_out_and_in() {
+        // doubles its input, and adds up two inputs
+        let double = parse_program("3,9,1002,9,2,9,4,9,99,0");
+        let add = parse_program("3,11,3,12,1,11,12,11,4,11,99,0,0");
+
+        let mut pipeline = Pipeline::new();
+        let source = pipeline.stage(double.clone(), vec![5]);
+        let left = pipeline.stage(double.clone(), vec![]);
+        let right = pipeline.stage(double, vec![]);
+        let sum = pipeline.stage(add, vec![]);
+        pipeline
+            .edge(source, left)
+            .and_then(|pipeline| pipeline.edge(source, right))
+            .and_then(|pipeline| pipeline.edge(left, sum))
+            .and_then(|pipeline| pipeline.edge(right, sum))
+            .and_then(|pipeline| pipeline.output(sum))
+            .unwrap();
+
+        let result = pipeline.run().unwrap();
+        assert_eq!(result.signal, Some(40));
+        assert_eq!(result.history, vec![vec![10], vec![20], vec![20], vec![40]]);
+
+        assert_eq!(
+            pipeline.edge(sum, 4).unwrap_err(),
+            LinkError::UnknownMachine(4)
+        );
+        assert_eq!(
+            pipeline.output(4).unwrap_err(),
+            LinkError::UnknownMachine(4)
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{Execution, ExecutionState, Instruction, IntCode, Mode, OpCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// How many rows of addresses and blocks the table shows
+const TABLE_ROWS: usize = 10;
+
+/// A straight run of instructions between jumps. Blocks are found as we execute, so jumping in
+/// to the middle of one starts a separate block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    /// the address of the last instruction we've seen in this block
+    pub end: usize,
+    pub entries: usize,
+    pub instructions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    instructions: usize,
+    starved: usize,
+    op_codes: HashMap<OpCode, usize>,
+    /// keyed by the canonical instruction word, which is unique for every op code and mode combo.
+    /// We keep the instruction as well, since extension op codes only decode with their extensions
+    modes: HashMap<IntCode, (Instruction, usize)>,
+    addresses: HashMap<usize, usize>,
+    blocks: HashMap<usize, Block>,
+    current_block: Option<usize>,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.instructions
+    }
+
+    /// How many times we stopped because there wasn't any input
+    pub fn starved(&self) -> usize {
+        self.starved
+    }
+
+    /// Executed instructions per op code, most executed first
+    pub fn op_codes(&self) -> Vec<(OpCode, usize)> {
+        sorted(self.op_codes.iter().map(|(o, &count)| (*o, count)))
+    }
+
+    /// Executed instructions per op code and mode combination, most executed first
+    pub fn modes(&self) -> Vec<(Instruction, usize)> {
+        sorted(self.modes.values().map(|(i, count)| (*i, *count)))
+    }
+
+    /// How many times each address executed, hottest first
+    pub fn addresses(&self) -> Vec<(usize, usize)> {
+        sorted(self.addresses.iter().map(|(&a, &count)| (a, count)))
+    }
+
+    /// Blocks by how many instructions they executed, hottest first
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks: Vec<Block> = self.blocks.values().cloned().collect();
+        blocks.sort_by_key(|block| (std::cmp::Reverse(block.instructions), block.start));
+
+        blocks
+    }
+
+    pub(super) fn record(&mut self, ip: usize, instruction: &Instruction, state: &ExecutionState) {
+        // starving doesn't execute anything, we'll try the same instruction again later
+        if *state == ExecutionState::NeedsInput {
+            self.starved += 1;
+            return;
+        }
+
+        self.instructions += 1;
+        *self
+            .op_codes
+            .entry(*instruction.op_code())
+            .or_default() += 1;
+        self.modes
+            .entry(instruction.encode())
+            .or_insert_with(|| (*instruction, 0))
+            .1 += 1;
+        *self.addresses.entry(ip).or_default() += 1;
+
+        let start = *self.current_block.get_or_insert(ip);
+        let block = self.blocks.entry(start).or_insert(Block {
+            start,
+            end: ip,
+            entries: 0,
+            instructions: 0,
+        });
+        if start == ip {
+            block.entries += 1;
+        }
+        block.end = block.end.max(ip);
+        block.instructions += 1;
+
+        match instruction.op_code() {
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse | OpCode::Halt => self.current_block = None,
+            _ => (),
+        }
+    }
+
+    /// Everything we counted as JSON
+    pub fn to_json(&self) -> String {
+        let counts = |counts: Vec<(String, usize)>| {
+            counts
+                .iter()
+                .map(|(key, count)| format!("\"{}\":{}", key, count))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let op_codes = self
+            .op_codes()
+            .into_iter()
+            .map(|(op_code, count)| (op_code.mnemonic().to_string(), count))
+            .collect();
+        let modes = self
+            .modes()
+            .into_iter()
+            .map(|(instruction, count)| (instruction.encode().to_string(), count))
+            .collect();
+        let addresses = self
+            .addresses()
+            .into_iter()
+            .map(|(address, count)| (address.to_string(), count))
+            .collect();
+        let blocks = self
+            .blocks()
+            .iter()
+            .map(|block| {
+                format!(
+                    "{{\"start\":{},\"end\":{},\"entries\":{},\"instructions\":{}}}",
+                    block.start, block.end, block.entries, block.instructions
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "{{\"instructions\":{},\"starved\":{},\"op_codes\":{{{}}},\"modes\":{{{}}},\"addresses\":{{{}}},\"blocks\":[{}]}}",
+            self.instructions,
+            self.starved,
+            counts(op_codes),
+            counts(modes),
+            counts(addresses),
+            blocks
+        )
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let percent = |count: usize| 100.0 * count as f64 / self.instructions.max(1) as f64;
+
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "input starvation pauses: {}", self.starved)?;
+
+        writeln!(f, "\nop code      executed")?;
+        for (op_code, count) in self.op_codes() {
+            writeln!(
+                f,
+                "{:<8} {:>12} {:>6.2}%",
+                op_code.mnemonic(),
+                count,
+                percent(count)
+            )?;
+        }
+
+        writeln!(f, "\nmodes                   executed")?;
+        for (instruction, count) in self.modes() {
+            let modes: Vec<&str> = instruction
+                .modes()
+                .iter()
+                .map(|mode| match mode {
+                    Mode::Position => "[a]",
+                    Mode::Immediate => "#i",
+                    Mode::Relative => "rb+o",
+                })
+                .collect();
+            let modes = format!("{} {}", instruction.op_code().mnemonic(), modes.join(", "));
+
+            writeln!(f, "{:<19} {:>12} {:>6.2}%", modes, count, percent(count))?;
+        }
+
+        writeln!(f, "\naddress      executed")?;
+        for (address, count) in self.addresses().into_iter().take(TABLE_ROWS) {
+            writeln!(f, "{:>7} {:>13} {:>6.2}%", address, count, percent(count))?;
+        }
+
+        writeln!(f, "\nblock          entries instructions")?;
+        for block in self.blocks().into_iter().take(TABLE_ROWS) {
+            writeln!(
+                f,
+                "{:>5}-{:<5} {:>10} {:>12} {:>6.2}%",
+                block.start,
+                block.end,
+                block.entries,
+                block.instructions,
+                percent(block.instructions)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn sorted<K: Ord, I: Iterator<Item = (K, usize)>>(counts: I) -> Vec<(K, usize)> {
+    let mut counts: Vec<(K, usize)> = counts.collect();
+    counts.sort_by(|(a_key, a), (b_key, b)| b.cmp(a).then_with(|| a_key.cmp(b_key)));
+
+    counts
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::new());
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn profile() {
+        let program = assemble(
+            r#"
+                    IN [count]
+            loop:   ADD [count], #-1, [count]
+                    JT [count], #loop
+                    OUT [count]
+                    HLT
+            count:  DATA 0
+            "#,
+        )
+        .unwrap();
+
+        let mut execution = Execution::new(program);
+        execution.start_profile();
+        assert_eq!(execution.run().unwrap(), ExecutionState::NeedsInput);
+        execution.input.push_back(3);
+        assert_eq!(execution.run().unwrap(), ExecutionState::Halted);
+
+        let profile = execution.take_profile().unwrap();
+        assert_eq!(profile.instructions(), 9);
+        assert_eq!(profile.starved(), 1);
+        assert_eq!(
+            profile.op_codes(),
+            vec![
+                (OpCode::Add, 3),
+                (OpCode::JumpIfTrue, 3),
+                (OpCode::Input, 1),
+                (OpCode::Output, 1),
+                (OpCode::Halt, 1)
+            ]
+        );
+        assert_eq!(profile.addresses()[0], (2, 3));
+        assert_eq!(
+            profile.blocks(),
+            vec![
+                Block {
+                    start: 2,
+                    end: 6,
+                    entries: 2,
+                    instructions: 4
+                },
+                Block {
+                    start: 0,
+                    end: 6,
+                    entries: 1,
+                    instructions: 3
+                },
+                Block {
+                    start: 9,
+                    end: 11,
+                    entries: 1,
+                    instructions: 2
+                },
+            ]
+        );
+
+        assert!(profile
+            .to_string()
+            .contains("ADD [a], #i, [a]               3  33.33%"));
+        assert!(profile.to_json().starts_with(
+            "{\"instructions\":9,\"starved\":1,\"op_codes\":{\"ADD\":3,\"JT\":3,\"IN\":1,"
+        ));
+
+        // the mode of a parameter OUT doesn't have can't split its row
+        let mut execution = Execution::new(parse_program("104,1,1104,2,99"));
+        execution.start_profile();
+        execution.run().unwrap();
+        let profile = execution.take_profile().unwrap();
+        assert_eq!(profile.modes()[0], (Instruction::new(104).unwrap(), 2));
+        assert!(profile.to_json().contains("\"modes\":{\"104\":2,\"99\":1}"));
+    }
+}
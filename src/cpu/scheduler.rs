@@ -0,0 +1,494 @@
+use crate::cpu::{CPUError, Execution, ExecutionState, IntCode};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// How many instructions each machine gets per turn by default
+pub const DEFAULT_TIME_SLICE: usize = 1_000;
+
+pub type MachineId = usize;
+
+/// Where a machine's output goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Link {
+    /// every output is queued as input for the other machine
+    Direct(MachineId),
+    /// outputs are packets of `size` words starting with their destination, which is followed by
+    /// the rest of the packet. Packets for machines we don't have are kept for the host
+    Packets { size: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    pub from: MachineId,
+    pub destination: IntCode,
+    pub payload: Vec<IntCode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SchedulerState {
+    /// someone still has work to do
+    Running,
+    AllHalted,
+    /// everyone left is waiting for input nobody will send
+    Deadlock,
+    /// everyone left is polling for input and nothing was sent for a whole round
+    Idle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineError {
+    pub machine: MachineId,
+    pub error: CPUError,
+}
+
+impl Display for MachineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "machine {}: {}", self.machine, self.error)
+    }
+}
+
+impl std::error::Error for MachineError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkError {
+    UnknownMachine(MachineId),
+    /// packets need at least a destination, and every packet link from a machine must agree on
+    /// where one packet ends
+    InvalidPacketSize {
+        machine: MachineId,
+        size: usize,
+    },
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::UnknownMachine(machine) => write!(f, "there is no machine {}", machine),
+            LinkError::InvalidPacketSize { machine, size } => write!(
+                f,
+                "machine {} can't send packets of {} words",
+                machine, size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub execution: Execution,
+    /// fed in whenever the machine looks at an empty input queue, like day 23's -1
+    pub idle_input: Option<IntCode>,
+    pub state: ExecutionState,
+    links: Vec<Link>,
+    /// whether we fed in `idle_input` this round
+    polled: bool,
+    history: Option<Vec<IntCode>>,
+}
+
+impl Machine {
+    /// Everything this machine has output, if we're recording history. Outputs that haven't been
+    /// sent anywhere are still in `execution.output`
+    pub fn history(&self) -> Option<Vec<IntCode>> {
+        self.history.as_ref().map(|history| {
+            history
+                .iter()
+                .chain(self.execution.output.iter())
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+/// Runs a network of executions round robin, giving each the same number of instructions per turn
+/// and routing their outputs through links
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    machines: Vec<Machine>,
+    unrouted: VecDeque<Packet>,
+    time_slice: usize,
+    rounds: usize,
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler {
+            machines: vec![],
+            unrouted: VecDeque::new(),
+            time_slice: DEFAULT_TIME_SLICE,
+            rounds: 0,
+        }
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    pub fn add(&mut self, execution: Execution) -> MachineId {
+        self.machines.push(Machine {
+            execution,
+            idle_input: None,
+            state: ExecutionState::Running,
+            links: vec![],
+            polled: false,
+            history: None,
+        });
+
+        self.machines.len() - 1
+    }
+
+    /// Sends `from`'s output to `link`, linking more than once sends each output everywhere. A
+    /// machine with a `Packets` link only sends whole packets, even along its direct links. Every
+    /// packet link from a machine has to use the same size
+    pub fn link(&mut self, from: MachineId, link: Link) -> Result<&mut Scheduler, LinkError> {
+        let machines = self.machines.len();
+        let machine = self
+            .machines
+            .get_mut(from)
+            .ok_or(LinkError::UnknownMachine(from))?;
+
+        match link {
+            Link::Direct(to) if to >= machines => return Err(LinkError::UnknownMachine(to)),
+            Link::Packets { size } => {
+                let agrees = machine.links.iter().all(|link| match *link {
+                    Link::Packets { size: other } => other == size,
+                    Link::Direct(_) => true,
+                });
+                if size == 0 || !agrees {
+                    return Err(LinkError::InvalidPacketSize {
+                        machine: from,
+                        size,
+                    });
+                }
+            }
+            Link::Direct(_) => (),
+        }
+        machine.links.push(link);
+
+        Ok(self)
+    }
+
+    pub fn set_idle_input(
+        &mut self,
+        machine: MachineId,
+        value: IntCode,
+    ) -> Result<&mut Scheduler, LinkError> {
+        self.machine_mut(machine)
+            .ok_or(LinkError::UnknownMachine(machine))?
+            .idle_input = Some(value);
+        Ok(self)
+    }
+
+    /// Keeps every output `machine` sends, so we can see it after it's been routed
+    pub fn record_history(&mut self, machine: MachineId) -> Result<&mut Scheduler, LinkError> {
+        self.machine_mut(machine)
+            .ok_or(LinkError::UnknownMachine(machine))?
+            .history
+            .get_or_insert_with(Vec::new);
+        Ok(self)
+    }
+
+    pub fn set_time_slice(&mut self, time_slice: usize) -> &mut Scheduler {
+        self.time_slice = time_slice.max(1);
+        self
+    }
+
+    pub fn machine(&self, machine: MachineId) -> Option<&Machine> {
+        self.machines.get(machine)
+    }
+
+    pub fn machine_mut(&mut self, machine: MachineId) -> Option<&mut Machine> {
+        self.machines.get_mut(machine)
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    /// How many rounds we've run
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Takes the packets that were addressed to machines we don't have
+    pub fn take_unrouted(&mut self) -> Vec<Packet> {
+        self.unrouted.drain(..).collect()
+    }
+
+    /// Gives every machine that hasn't halted one time slice
+    pub fn round(&mut self) -> Result<SchedulerState, MachineError> {
+        self.rounds += 1;
+
+        let mut sent = false;
+        for id in 0..self.machines.len() {
+            let machine = &mut self.machines[id];
+            if machine.state == ExecutionState::Halted {
+                continue;
+            }
+
+            machine.polled = false;
+            if machine.execution.input.is_empty() {
+                if let Some(value) = machine.idle_input {
+                    machine.execution.input.push_back(value);
+                    machine.polled = true;
+                }
+            }
+            machine.state = machine
+                .execution
+                .run_for(self.time_slice)
+                .map_err(|error| MachineError { machine: id, error })?;
+
+            sent |= self.route(id);
+        }
+
+        Ok(self.state(sent))
+    }
+
+    /// Runs rounds until everyone halts, deadlocks or goes idle
+    pub fn run(&mut self) -> Result<SchedulerState, MachineError> {
+        loop {
+            match self.round()? {
+                SchedulerState::Running => (),
+                state => return Ok(state),
+            }
+        }
+    }
+
+    /// Moves `from`'s output along its links, returning whether anything was sent
+    fn route(&mut self, from: MachineId) -> bool {
+        let machine = &mut self.machines[from];
+        if machine.links.is_empty() || machine.execution.output.is_empty() {
+            return false;
+        }
+
+        // anything past the last whole packet is part of a packet that isn't finished yet
+        let packet_size = machine.links.iter().find_map(|link| match link {
+            Link::Packets { size } => Some(*size),
+            _ => None,
+        });
+        let output = &mut machine.execution.output;
+        let finished = match packet_size {
+            Some(size) => output.len() / size * size,
+            None => output.len(),
+        };
+        let sent: Vec<IntCode> = output.drain(..finished).collect();
+        if let Some(history) = machine.history.as_mut() {
+            history.extend(sent.iter().cloned());
+        }
+
+        for link in machine.links.clone() {
+            match link {
+                Link::Direct(to) => self.machines[to].execution.input.extend(sent.iter()),
+                Link::Packets { size } => {
+                    for packet in sent.chunks(size) {
+                        self.send(Packet {
+                            from,
+                            destination: packet[0],
+                            payload: packet[1..].to_vec(),
+                        });
+                    }
+                }
+            }
+        }
+
+        !sent.is_empty()
+    }
+
+    fn send(&mut self, packet: Packet) {
+        match self.machines.get_mut(packet.destination as usize) {
+            Some(machine) if packet.destination >= 0 => {
+                machine.execution.input.extend(packet.payload)
+            }
+            _ => self.unrouted.push_back(packet),
+        }
+    }
+
+    fn state(&self, sent: bool) -> SchedulerState {
+        let mut waiting = self
+            .machines
+            .iter()
+            .filter(|machine| machine.state != ExecutionState::Halted)
+            .peekable();
+
+        if waiting.peek().is_none() {
+            return SchedulerState::AllHalted;
+        }
+
+        let mut polling = false;
+        let mut all_polled = true;
+        for machine in waiting {
+            if machine.state != ExecutionState::NeedsInput || !machine.execution.input.is_empty() {
+                return SchedulerState::Running;
+            }
+            polling |= machine.idle_input.is_some();
+            // anyone who hasn't been told there's nothing for them yet might still send something
+            all_polled &= machine.idle_input.is_none() || machine.polled;
+        }
+
+        match (polling, sent || !all_polled) {
+            (false, _) => SchedulerState::Deadlock,
+            (true, false) => SchedulerState::Idle,
+            (true, true) => SchedulerState::Running,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn ring() {
+        // adds one to every value it sees, halting after passing on 10
+        let program = assemble(
+            r#"
+            loop:   IN [value]
+                    ADD [value], #1, [value]
+                    OUT [value]
+                    LT [value], #10, [more]
+                    JT [more], #loop
+                    HLT
+            value:  DATA 0
+            more:   DATA 0
+            "#,
+        )
+        .unwrap();
+
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.add(Execution::new_input(program.clone(), vec![0]));
+        let b = scheduler.add(Execution::new(program));
+        scheduler
+            .link(a, Link::Direct(b))
+            .and_then(|scheduler| scheduler.link(b, Link::Direct(a)))
+            .unwrap()
+            .set_time_slice(3);
+
+        assert_eq!(scheduler.run(), Ok(SchedulerState::AllHalted));
+        assert_eq!(scheduler.machine(a).unwrap().execution[16], 11);
+        assert_eq!(scheduler.machine(b).unwrap().execution[16], 10);
+        assert_eq!(scheduler.machine(b).unwrap().execution.input, vec![11]);
+    }
+
+    #[test]
+    fn mixed_links() {
+        // the direct link only sees whole packets, and sees each of them once
+        let program = parse_program("104,7,104,3,104,9,104,5,99");
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.add(Execution::new(program));
+        let b = scheduler.add(Execution::new(vec![99]));
+        scheduler
+            .link(a, Link::Direct(b))
+            .and_then(|scheduler| scheduler.link(a, Link::Packets { size: 2 }))
+            .unwrap()
+            .set_time_slice(3);
+
+        assert_eq!(scheduler.run(), Ok(SchedulerState::AllHalted));
+        assert_eq!(
+            scheduler.machine(b).unwrap().execution.input,
+            vec![7, 3, 9, 5]
+        );
+        assert_eq!(
+            scheduler
+                .take_unrouted()
+                .iter()
+                .map(|packet| packet.destination)
+                .collect::<Vec<_>>(),
+            vec![7, 9]
+        );
+
+        assert_eq!(
+            scheduler.link(a, Link::Packets { size: 3 }).unwrap_err(),
+            LinkError::InvalidPacketSize {
+                machine: a,
+                size: 3
+            }
+        );
+        assert_eq!(
+            scheduler.link(b, Link::Packets { size: 0 }).unwrap_err(),
+            LinkError::InvalidPacketSize {
+                machine: b,
+                size: 0
+            }
+        );
+        assert_eq!(
+            scheduler.link(a, Link::Direct(2)).unwrap_err(),
+            LinkError::UnknownMachine(2)
+        );
+        assert_eq!(
+            scheduler.link(2, Link::Direct(a)).unwrap_err().to_string(),
+            "there is no machine 2"
+        );
+    }
+
+    #[test]
+    fn deadlock_and_idle() {
+        // a passes one value to b, then both wait for more
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.add(Execution::new_input(
+            parse_program("3,0,4,0,3,0,99"),
+            vec![5],
+        ));
+        let b = scheduler.add(Execution::new(parse_program("3,0,4,0,3,0,99")));
+        scheduler.link(a, Link::Direct(b)).unwrap();
+        assert_eq!(scheduler.run(), Ok(SchedulerState::Deadlock));
+        assert_eq!(scheduler.machine(b).unwrap().execution.output, vec![5]);
+
+        // polls with -1, sending anything else on to 7, which nobody is
+        let program = assemble(
+            r#"
+            loop:   IN [value]
+                    EQ [value], #-1, [skip]
+                    JT [skip], #loop
+                    OUT #7
+                    OUT [value]
+                    JT #1, #loop
+            value:  DATA 0
+            skip:   DATA 0
+            "#,
+        )
+        .unwrap();
+        let mut scheduler = Scheduler::new();
+        let c = scheduler.add(Execution::new_input(program, vec![3]));
+        scheduler
+            .link(c, Link::Packets { size: 2 })
+            .and_then(|scheduler| scheduler.set_idle_input(c, -1))
+            .unwrap();
+
+        assert_eq!(scheduler.run(), Ok(SchedulerState::Idle));
+        assert_eq!(scheduler.rounds(), 2);
+        assert_eq!(
+            scheduler.take_unrouted(),
+            vec![Packet {
+                from: c,
+                destination: 7,
+                payload: vec![3]
+            }]
+        );
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(Execution::new(vec![42]));
+        assert!(scheduler.machine(1).is_none());
+        assert_eq!(
+            scheduler.set_idle_input(1, -1).unwrap_err(),
+            LinkError::UnknownMachine(1)
+        );
+        assert_eq!(
+            scheduler.record_history(1).unwrap_err(),
+            LinkError::UnknownMachine(1)
+        );
+        assert_eq!(
+            scheduler.run().unwrap_err().to_string(),
+            "machine 0: invalid op code in 42 at ip 0"
+        );
+    }
+}
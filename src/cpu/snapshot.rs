@@ -0,0 +1,342 @@
+use crate::cpu::extensions::Extensions;
+use crate::cpu::{try_parse_program, Arithmetic, Execution, IntCode};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+static MAGIC: &str = "intcode-snapshot";
+pub const SNAPSHOT_VERSION: usize = 2;
+static FIELDS: [&str; 9] = [
+    "ip",
+    "relative_base",
+    "arithmetic",
+    "memory_limit",
+    "extensions",
+    "memory",
+    "pages",
+    "input",
+    "output",
+];
+static V1_FIELDS: [&str; 5] = ["ip", "relative_base", "memory", "input", "output"];
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    UnsupportedVersion(String),
+    Malformed {
+        line: usize,
+        message: String,
+    },
+    /// the snapshot ran with an extension we weren't given, so it can't carry on the same way
+    MissingExtension {
+        code: IntCode,
+        mnemonic: String,
+    },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {:?}", version)
+            }
+            SnapshotError::Malformed { line, message } => write!(f, "line {}: {}", line, message),
+            SnapshotError::MissingExtension { code, mnemonic } => write!(
+                f,
+                "the snapshot uses extension {} ({}), which isn't registered",
+                mnemonic, code
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+/// Snapshots are plain text so they can be diffed or attached to a bug report:
+/// ```text
+/// intcode-snapshot 2
+/// ip 2
+/// relative_base 0
+/// arithmetic checked
+/// memory_limit 100000
+/// extensions 42=TRAP
+/// memory 3,9,4,9,99
+/// pages 1000000=5
+/// input 7,8
+/// output
+/// ```
+/// `memory_limit` is `none` when there isn't one. Extension handlers are code, so only their op
+/// codes are saved, and loading needs them registered again with `load_with`. Version 1 snapshots
+/// have just the registers, memory and io, and load with the default arithmetic and no limit
+impl Execution {
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{} {}", MAGIC, SNAPSHOT_VERSION)?;
+        writeln!(writer, "ip {}", self.ip)?;
+        writeln!(writer, "relative_base {}", self.relative_base)?;
+        let arithmetic = match self.arithmetic {
+            Arithmetic::Wrapping => "wrapping",
+            Arithmetic::Checked => "checked",
+            Arithmetic::Saturating => "saturating",
+        };
+        writeln!(writer, "arithmetic {}", arithmetic)?;
+        match self.memory_limit {
+            Some(limit) => writeln!(writer, "memory_limit {}", limit)?,
+            None => writeln!(writer, "memory_limit none")?,
+        }
+        let extensions: Vec<String> = self
+            .extensions
+            .registered()
+            .into_iter()
+            .map(|extension| format!("{}={}", extension.code, extension.mnemonic))
+            .collect();
+        writeln!(writer, "extensions {}", extensions.join(","))?;
+        writeln!(writer, "memory {}", join(self.memory.iter()))?;
+        let cells: Vec<String> = self
+            .pages
+            .cells()
+            .into_iter()
+            .map(|(address, value)| format!("{}={}", address, value))
+            .collect();
+        writeln!(writer, "pages {}", cells.join(","))?;
+        writeln!(writer, "input {}", join(self.input.iter()))?;
+        writeln!(writer, "output {}", join(self.output.iter()))?;
+
+        writer.flush()
+    }
+
+    /// Loads a snapshot that doesn't use any extensions
+    pub fn load<R: BufRead>(reader: R) -> Result<Execution, SnapshotError> {
+        Execution::load_with(reader, &Extensions::new())
+    }
+
+    /// Loads a snapshot, running it with `extensions`. Every extension the snapshot was saved
+    /// with has to be in there
+    pub fn load_with<R: BufRead>(
+        reader: R,
+        extensions: &Extensions,
+    ) -> Result<Execution, SnapshotError> {
+        let mut lines = reader.lines();
+
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let fields: &[&str] = match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [magic, "1"] if *magic == MAGIC => &V1_FIELDS,
+            [magic, "2"] if *magic == MAGIC => &FIELDS,
+            [magic, version] if *magic == MAGIC => {
+                return Err(SnapshotError::UnsupportedVersion(version.to_string()));
+            }
+            _ => {
+                return Err(SnapshotError::Malformed {
+                    line: 1,
+                    message: format!("expected a {} header", MAGIC),
+                });
+            }
+        };
+
+        let mut execution = Execution::new(vec![]);
+        execution.extensions = extensions.clone();
+        for (i, field) in fields.iter().enumerate() {
+            let line = i + 2;
+            let malformed = |message: String| SnapshotError::Malformed { line, message };
+
+            let raw_line = lines
+                .next()
+                .transpose()?
+                .ok_or_else(|| malformed(format!("missing {}", field)))?;
+            let mut parts = raw_line.splitn(2, ' ');
+            if parts.next() != Some(field) {
+                return Err(malformed(format!("expected {}", field)));
+            }
+
+            let value = parts.next().unwrap_or("");
+            match *field {
+                "ip" | "relative_base" => {
+                    let register = value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|e| malformed(format!("bad {}: {}", field, e)))?;
+
+                    if *field == "ip" {
+                        execution.ip = register;
+                    } else {
+                        execution.relative_base = register;
+                    }
+                }
+                "arithmetic" => {
+                    execution.arithmetic = match value.trim() {
+                        "wrapping" => Arithmetic::Wrapping,
+                        "checked" => Arithmetic::Checked,
+                        "saturating" => Arithmetic::Saturating,
+                        other => return Err(malformed(format!("bad arithmetic {:?}", other))),
+                    }
+                }
+                "memory_limit" => {
+                    execution.memory_limit = match value.trim() {
+                        "none" => None,
+                        limit => Some(
+                            limit
+                                .parse::<usize>()
+                                .map_err(|e| malformed(format!("bad memory_limit: {}", e)))?,
+                        ),
+                    }
+                }
+                "extensions" => {
+                    for saved in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                        let mut parts = saved.splitn(2, '=');
+                        let code = parts
+                            .next()
+                            .and_then(|c| c.parse::<IntCode>().ok())
+                            .ok_or_else(|| malformed(format!("bad extension {:?}", saved)))?;
+                        let mnemonic = parts.next().unwrap_or("").to_string();
+
+                        match extensions.get(code) {
+                            Some(extension) if extension.mnemonic == mnemonic => (),
+                            _ => return Err(SnapshotError::MissingExtension { code, mnemonic }),
+                        }
+                    }
+                }
+                "pages" => {
+                    for cell in value.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                        let mut parts = cell.splitn(2, '=');
+                        match (
+                            parts.next().and_then(|a| a.parse::<usize>().ok()),
+                            parts.next().and_then(|v| v.parse::<IntCode>().ok()),
+                        ) {
+                            (Some(address), Some(value)) => {
+                                *execution.pages.get_mut(address) = value
+                            }
+                            _ => return Err(malformed(format!("bad page cell {:?}", cell))),
+                        }
+                    }
+                }
+                _ => {
+                    let values = try_parse_program(value).map_err(|e| malformed(e.to_string()))?;
+
+                    match *field {
+                        "memory" => execution.memory = values,
+                        "input" => execution.input = values.into(),
+                        _ => execution.output = values.into(),
+                    }
+                }
+            }
+        }
+
+        Ok(execution)
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Execution, SnapshotError> {
+        Execution::load(BufReader::new(File::open(path)?))
+    }
+}
+
+fn join<'a, I: Iterator<Item = &'a IntCode>>(values: I) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn round_trip() {
+        // echo until we read a 0
+        let mut execution =
+            Execution::new_input(parse_program("3,11,1006,11,10,4,11,1105,1,0,99,0"), vec![3]);
+        execution.run().unwrap();
+        execution.input.extend(vec![7, 8]);
+
+        let mut saved = vec![];
+        execution.save(&mut saved).unwrap();
+        assert_eq!(
+            String::from_utf8(saved.clone()).unwrap(),
+            "intcode-snapshot 2\nip 0\nrelative_base 0\narithmetic wrapping\nmemory_limit none\nextensions \nmemory 3,11,1006,11,10,4,11,1105,1,0,99,3\npages \ninput 7,8\noutput 3\n"
+        );
+
+        let mut loaded = Execution::load(saved.as_slice()).unwrap();
+        assert_eq!(loaded.memory, execution.memory);
+        assert_eq!(loaded.input, execution.input);
+
+        loaded.input.push_back(0);
+        loaded.run().unwrap();
+        assert_eq!(Vec::from(loaded.output), vec![3, 7, 8]);
+
+        // older snapshots don't have any pages
+        let v1 = "intcode-snapshot 1\nip 0\nrelative_base 0\nmemory 3,11,1006,11,10,4,11,1105,1,0,99,3\ninput 7,8\noutput 3\n";
+        let loaded = Execution::load(v1.as_bytes()).unwrap();
+        assert_eq!(loaded.memory, execution.memory);
+        assert_eq!(loaded.input, execution.input);
+    }
+
+    #[test]
+    fn sparse_round_trip() {
+        let mut execution = Execution::new(parse_program("99"));
+        execution[2_000_000] = 5;
+        execution[2_000_001] = -7;
+
+        let mut saved = vec![];
+        execution.save(&mut saved).unwrap();
+        assert!(String::from_utf8(saved.clone())
+            .unwrap()
+            .contains("\npages 2000000=5,2000001=-7\n"));
+
+        let loaded = Execution::load(saved.as_slice()).unwrap();
+        assert_eq!(loaded.pages, execution.pages);
+        assert_eq!(loaded[2_000_001], -7);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut extensions = Extensions::new();
+        extensions.register(42, "NOP", &[], |_| Ok(()));
+
+        let mut execution = Execution::new(parse_program("42,1002,0,2,0,99"));
+        execution.arithmetic = Arithmetic::Checked;
+        execution.memory_limit = Some(100);
+        execution.extensions = extensions.clone();
+
+        let mut saved = vec![];
+        execution.save(&mut saved).unwrap();
+        assert!(String::from_utf8(saved.clone())
+            .unwrap()
+            .contains("\narithmetic checked\nmemory_limit 100\nextensions 42=NOP\n"));
+
+        let mut loaded = Execution::load_with(saved.as_slice(), &extensions).unwrap();
+        assert_eq!(loaded.arithmetic, Arithmetic::Checked);
+        assert_eq!(loaded.memory_limit, Some(100));
+        assert!(loaded.run().is_ok());
+
+        match Execution::load(saved.as_slice()) {
+            Err(SnapshotError::MissingExtension { code, mnemonic }) => {
+                assert_eq!((code, mnemonic.as_str()), (42, "NOP"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bad_snapshots() {
+        match Execution::load("intcode-snapshot 3\n".as_bytes()) {
+            Err(SnapshotError::UnsupportedVersion(version)) => assert_eq!(version, "3"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match Execution::load("intcode-snapshot 1\nip 0\nrelative_base x\n".as_bytes()) {
+            Err(SnapshotError::Malformed { line, .. }) => assert_eq!(line, 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use crate::cpu::{Execution, ExecutionState, IntCode, Memory};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::result;
+
+/// What we're looking for in a finished run
+pub enum Goal<'a> {
+    /// any run the predicate accepts
+    Predicate(&'a (dyn Fn(&Execution) -> bool + Sync)),
+    /// a run where the value we read from it equals the target, e.g. `execution[0]`
+    Value(&'a (dyn Fn(&Execution) -> IntCode + Sync), IntCode),
+}
+
+impl<'a> Goal<'a> {
+    pub fn is_met(&self, execution: &Execution) -> bool {
+        match self {
+            Goal::Predicate(predicate) => predicate(execution),
+            Goal::Value(measure, target) => measure(execution) == *target,
+        }
+    }
+}
+
+/// Somewhere a value we're searching for goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    /// patched in to memory before we run
+    Cell(usize),
+    /// queued as input, in the order the inputs were added
+    Input,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolverError {
+    /// there are more combinations of values than fit in a `usize`, so we can't count through them
+    TooManyCombinations,
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SolverError::TooManyCombinations => {
+                write!(f, "there are too many combinations of values to search")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+type Result<T> = result::Result<T, SolverError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputSlot {
+    Fixed(IntCode),
+    Parameter(usize),
+}
+
+/// Searches for values of memory cells or inputs that make a program reach a goal, like day 2's
+/// noun and verb. Runs that fail or run out of budget never meet the goal
+#[derive(Debug, Clone)]
+pub struct Solver {
+    program: Memory,
+    parameters: Vec<(Parameter, RangeInclusive<IntCode>)>,
+    input: Vec<InputSlot>,
+    budget: Option<usize>,
+}
+
+impl Solver {
+    pub fn new(program: Memory) -> Solver {
+        Solver {
+            program,
+            parameters: vec![],
+            input: vec![],
+            budget: None,
+        }
+    }
+
+    /// Searches `values` for the memory cell at `address`
+    pub fn cell(mut self, address: usize, values: RangeInclusive<IntCode>) -> Solver {
+        self.parameters.push((Parameter::Cell(address), values));
+        self
+    }
+
+    /// Searches `values` for the next input
+    pub fn input(mut self, values: RangeInclusive<IntCode>) -> Solver {
+        self.input.push(InputSlot::Parameter(self.parameters.len()));
+        self.parameters.push((Parameter::Input, values));
+        self
+    }
+
+    /// Queues an input that's the same for every run
+    pub fn fixed_input(mut self, value: IntCode) -> Solver {
+        self.input.push(InputSlot::Fixed(value));
+        self
+    }
+
+    /// Gives up on any run that takes more than `budget` instructions
+    pub fn budget(mut self, budget: usize) -> Solver {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn parameters(&self) -> &[(Parameter, RangeInclusive<IntCode>)] {
+        &self.parameters
+    }
+
+    /// How many combinations of values there are to search
+    pub fn size(&self) -> Result<usize> {
+        self.parameters
+            .iter()
+            .try_fold(1usize, |size, (_, range)| {
+                size.checked_mul(range_len(range)?)
+            })
+            .ok_or(SolverError::TooManyCombinations)
+    }
+
+    /// Runs the program with one value per parameter, `None` if it didn't halt
+    pub fn evaluate(&self, values: &[IntCode]) -> Option<Execution> {
+        let input: Vec<IntCode> = self
+            .input
+            .iter()
+            .map(|slot| match *slot {
+                InputSlot::Fixed(value) => value,
+                InputSlot::Parameter(i) => values[i],
+            })
+            .collect();
+
+        let mut execution = Execution::new_input(self.program.clone(), input);
+        for ((parameter, _), &value) in self.parameters.iter().zip(values) {
+            if let Parameter::Cell(address) = *parameter {
+                *execution.cell_mut(address).ok()? = value;
+            }
+        }
+
+        let state = match self.budget {
+            Some(budget) => execution.run_for(budget),
+            None => execution.run(),
+        };
+
+        match state {
+            Ok(ExecutionState::Halted) => Some(execution),
+            _ => None,
+        }
+    }
+
+    pub fn solve<S: Strategy>(&self, strategy: &S, goal: &Goal) -> Result<Option<Vec<IntCode>>> {
+        strategy.search(self, goal)
+    }
+
+    fn meets(&self, values: &[IntCode], goal: &Goal) -> bool {
+        self.evaluate(values)
+            .is_some_and(|execution| goal.is_met(&execution))
+    }
+
+    /// The values for the `index`th combination, the first parameter changing slowest. Only call
+    /// this once `size` has checked every range fits in a `usize`
+    fn values(&self, mut index: usize) -> Vec<IntCode> {
+        let mut values = vec![0; self.parameters.len()];
+        for (value, (_, range)) in values.iter_mut().zip(self.parameters.iter()).rev() {
+            let len = range_len(range).expect("Ranges are checked by size");
+            // the offset can be past IntCode::MAX, but the value it lands on is in the range, so
+            // wrapping gets us there
+            *value = range.start().wrapping_add((index % len) as IntCode);
+            index /= len;
+        }
+
+        values
+    }
+}
+
+/// How many values are in `range`, `None` if that doesn't fit in a `usize`
+fn range_len(range: &RangeInclusive<IntCode>) -> Option<usize> {
+    if range.is_empty() {
+        Some(0)
+    } else {
+        let last = (*range.end() as i128) - (*range.start() as i128);
+        usize::try_from(last).ok()?.checked_add(1)
+    }
+}
+
+pub trait Strategy {
+    /// The first values we find that meet the goal
+    fn search(&self, solver: &Solver, goal: &Goal) -> Result<Option<Vec<IntCode>>>;
+}
+
+/// Tries every combination in order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exhaustive;
+
+impl Strategy for Exhaustive {
+    fn search(&self, solver: &Solver, goal: &Goal) -> Result<Option<Vec<IntCode>>> {
+        Ok((0..solver.size()?)
+            .map(|index| solver.values(index))
+            .find(|values| solver.meets(values, goal)))
+    }
+}
+
+/// Tries every combination split across threads, still finding the same values as `Exhaustive`
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelExhaustive {
+    pub threads: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for ParallelExhaustive {
+    fn default() -> ParallelExhaustive {
+        ParallelExhaustive {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Strategy for ParallelExhaustive {
+    fn search(&self, solver: &Solver, goal: &Goal) -> Result<Option<Vec<IntCode>>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let size = solver.size()?;
+        let threads = self.threads.max(1);
+        // the lowest index anyone has found, so everyone can stop once they're past it
+        let found = AtomicUsize::new(size);
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let found = &found;
+                scope.spawn(move || {
+                    let mut index = thread;
+                    while index < found.load(Ordering::Relaxed) {
+                        if solver.meets(&solver.values(index), goal) {
+                            found.fetch_min(index, Ordering::Relaxed);
+                            break;
+                        }
+                        index += threads;
+                    }
+                });
+            }
+        });
+
+        let found = found.into_inner();
+        if found < size {
+            Ok(Some(solver.values(found)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// For `Goal::Value` searches where the value changes linearly with every parameter, like day 2.
+/// We measure how much each parameter moves the value, then only need to try the combinations of
+/// every parameter but the last, solving for the last directly. Anything we find is confirmed by a
+/// real run. If the value turns out not to be affine, the model overflows an `IntCode`, the model
+/// doesn't lead to a solution, or the goal is a predicate, we fall back to `fallback`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Affine<S = Exhaustive> {
+    pub fallback: S,
+}
+
+impl Affine {
+    pub fn new() -> Affine {
+        Affine::default()
+    }
+}
+
+impl<S: Strategy> Strategy for Affine<S> {
+    fn search(&self, solver: &Solver, goal: &Goal) -> Result<Option<Vec<IntCode>>> {
+        let size = solver.size()?;
+        match goal {
+            Goal::Value(measure, target) if size > 0 => {
+                let solution = affine_model(solver, size, *measure)
+                    .and_then(|(base, slopes)| solve_affine(solver, goal, *target, base, &slopes));
+                match solution {
+                    Some(values) => Ok(Some(values)),
+                    // the model only saw a few runs, so it could be wrong anywhere else
+                    None => self.fallback.search(solver, goal),
+                }
+            }
+            _ => self.fallback.search(solver, goal),
+        }
+    }
+}
+
+/// How many combinations we run to check the model, besides the ones we built it from
+const MODEL_CHECKS: usize = 8;
+
+/// The value at the lowest values and how much it moves per step of each parameter
+fn affine_model(
+    solver: &Solver,
+    size: usize,
+    measure: &(dyn Fn(&Execution) -> IntCode + Sync),
+) -> Option<(IntCode, Vec<IntCode>)> {
+    let measure_at = |values: &[IntCode]| solver.evaluate(values).map(|e| measure(&e));
+
+    let lowest: Vec<IntCode> = solver
+        .parameters
+        .iter()
+        .map(|(_, range)| *range.start())
+        .collect();
+    let base = measure_at(&lowest)?;
+
+    let mut slopes = vec![];
+    for (i, (_, range)) in solver.parameters.iter().enumerate() {
+        if range.start() == range.end() {
+            slopes.push(0);
+            continue;
+        }
+
+        let mut values = lowest.clone();
+        values[i] += 1;
+        slopes.push(measure_at(&values)?.checked_sub(base)?);
+    }
+
+    // check the model at evenly spread combinations, up to the highest corner
+    for check in 1..=MODEL_CHECKS {
+        let values = solver.values((size - 1) / MODEL_CHECKS * check);
+        if measure_at(&values)? != predict(solver, base, &slopes, &values)? {
+            return None;
+        }
+    }
+
+    Some((base, slopes))
+}
+
+/// What the model says the value is for `values`, `None` if that overflows
+fn predict(
+    solver: &Solver,
+    base: IntCode,
+    slopes: &[IntCode],
+    values: &[IntCode],
+) -> Option<IntCode> {
+    values
+        .iter()
+        .zip(solver.parameters.iter())
+        .zip(slopes.iter())
+        .try_fold(base, |value, ((v, (_, range)), slope)| {
+            v.checked_sub(*range.start())?
+                .checked_mul(*slope)?
+                .checked_add(value)
+        })
+}
+
+fn solve_affine(
+    solver: &Solver,
+    goal: &Goal,
+    target: IntCode,
+    base: IntCode,
+    slopes: &[IntCode],
+) -> Option<Vec<IntCode>> {
+    let (last, rest) = solver.parameters.split_last()?;
+    let last_range = &last.1;
+    let last_slope = *slopes.last()?;
+
+    // every combination of the other parameters, with the last at its lowest. `size` has already
+    // checked all of these fit
+    let rest_size: usize = rest
+        .iter()
+        .map(|(_, range)| range_len(range).expect("Ranges are checked by size"))
+        .product();
+    let last_len = range_len(last_range).expect("Ranges are checked by size");
+    (0..rest_size)
+        .map(|index| solver.values(index * last_len))
+        .filter_map(|mut values| {
+            let remaining = target.checked_sub(predict(solver, base, slopes, &values)?)?;
+
+            let steps = if last_slope == 0 {
+                if remaining != 0 {
+                    return None;
+                }
+                0
+            } else if remaining.checked_rem(last_slope)? == 0 {
+                remaining.checked_div(last_slope)?
+            } else {
+                return None;
+            };
+
+            let last_value = last_range.start().checked_add(steps)?;
+            if !last_range.contains(&last_value) {
+                return None;
+            }
+            *values.last_mut()? = last_value;
+
+            Some(values)
+        })
+        .find(|values| solver.meets(values, goal))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::assembler::assemble;
+    use crate::cpu::parse_program;
+
+    #[test]
+    fn strategies() {
+        // [0] = [15] * 100 + [16] * 3, both of which we patch
+        let program = parse_program("1002,15,100,13,1002,16,3,14,1,13,14,0,99,0,0,0,0");
+        let solver = Solver::new(program).cell(15, 0..=99).cell(16, 0..=99);
+        let measure = |execution: &Execution| execution[0];
+        let goal = Goal::Value(&measure, 4321);
+
+        assert_eq!(solver.solve(&Exhaustive, &goal), Ok(Some(vec![43, 7])));
+        assert_eq!(
+            solver.solve(&ParallelExhaustive { threads: 3 }, &goal),
+            Ok(Some(vec![43, 7]))
+        );
+        assert_eq!(solver.solve(&Affine::new(), &goal), Ok(Some(vec![43, 7])));
+
+        let never = Goal::Value(&measure, 1);
+        assert_eq!(solver.solve(&Affine::new(), &never), Ok(None));
+        assert_eq!(
+            solver.solve(&ParallelExhaustive::default(), &never),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn wide_ranges() {
+        let program = parse_program("1002,15,100,13,1002,16,3,14,1,13,14,0,99,0,0,0,0");
+        let measure = |execution: &Execution| execution[0];
+        let goal = Goal::Value(&measure, 4321);
+
+        let everything = Solver::new(program.clone()).cell(15, IntCode::MIN..=IntCode::MAX);
+        assert_eq!(everything.size(), Err(SolverError::TooManyCombinations));
+        assert_eq!(
+            everything.solve(&Affine::new(), &goal),
+            Err(SolverError::TooManyCombinations)
+        );
+
+        // counting the values in this range alone used to overflow
+        let program = parse_program("1001,5,0,0,99,0");
+        let solver = Solver::new(program).cell(5, 0..=IntCode::MAX);
+        assert_eq!(solver.size(), Ok(1 << 63));
+        assert_eq!(
+            solver.solve(&Affine::new(), &Goal::Value(&measure, 21)),
+            Ok(Some(vec![21]))
+        );
+    }
+
+    #[test]
+    fn not_quite_affine() {
+        // [result] = [x], except 37 gives 1000, which none of the model's checks will see
+        let program = assemble(
+            r#"
+                    EQ [x], #37, [hit]
+                    JT [hit], #special
+                    ADD [x], #0, [result]
+                    HLT
+            special: ADD #1000, #0, [result]
+                    HLT
+            x:      DATA 0
+            hit:    DATA 0
+            result: DATA 0
+            "#,
+        )
+        .unwrap();
+        let solver = Solver::new(program).cell(17, 0..=99);
+        let result = |execution: &Execution| execution[19];
+
+        assert_eq!(
+            solver.solve(&Affine::new(), &Goal::Value(&result, 1000)),
+            Ok(Some(vec![37]))
+        );
+        assert_eq!(
+            solver.solve(&Affine::new(), &Goal::Value(&result, 42)),
+            Ok(Some(vec![42]))
+        );
+    }
+
+    #[test]
+    fn inputs() {
+        // outputs the square of its second input, which isn't affine
+        let program = assemble(
+            r#"
+                    IN [ignored]
+                    IN [x]
+                    MUL [x], [x], [x]
+                    OUT [x]
+                    HLT
+            ignored: DATA 0
+            x:      DATA 0
+            "#,
+        )
+        .unwrap();
+        let solver = Solver::new(program).fixed_input(7).input(-10..=10);
+        let square = |execution: &Execution| execution.output[0];
+
+        assert_eq!(
+            solver.solve(&Affine::new(), &Goal::Value(&square, 49)),
+            Ok(Some(vec![-7]))
+        );
+        let small = |execution: &Execution| execution.output[0] < 5;
+        assert_eq!(
+            solver.solve(&Exhaustive, &Goal::Predicate(&small)),
+            Ok(Some(vec![-2]))
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::cpu::io::{Input, Output};
+use crate::cpu::{CPUError, Execution, ExecutionState, Instruction, IntCode, OpCode};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::result;
+
+/// Everything observable about a single executed instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub ip: usize,
+    pub op_code: OpCode,
+    /// the values of every parameter that was read, in order. A jump that isn't taken never reads
+    /// where it would have gone
+    pub reads: Vec<IntCode>,
+    /// the address and new value of every parameter that was written, in order
+    pub writes: Vec<(usize, IntCode)>,
+}
+
+impl TraceEntry {
+    pub fn input(&self) -> Option<IntCode> {
+        match self.op_code {
+            OpCode::Input => self.writes.first().map(|&(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn output(&self) -> Option<IntCode> {
+        match self.op_code {
+            OpCode::Output => self.reads.first().cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}: {}", self.ip, self.op_code.mnemonic())?;
+        for read in self.reads.iter() {
+            write!(f, " {}", read)?;
+        }
+        for (address, value) in self.writes.iter() {
+            write!(f, " -> [{}]={}", address, value)?;
+        }
+        if let Some(input) = self.input() {
+            write!(f, " in={}", input)?;
+        }
+        if let Some(output) = self.output() {
+            write!(f, " out={}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where a replay stopped matching its trace
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    Mismatch {
+        index: usize,
+        expected: Box<TraceEntry>,
+        actual: Box<TraceEntry>,
+    },
+    Stopped {
+        index: usize,
+        expected: Box<TraceEntry>,
+        state: ExecutionState,
+    },
+    Error {
+        index: usize,
+        expected: Box<TraceEntry>,
+        error: CPUError,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace::default()
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(super) fn record(
+        &mut self,
+        ip: usize,
+        instruction: &Instruction,
+        state: &ExecutionState,
+        reads: Vec<IntCode>,
+        writes: Vec<(usize, IntCode)>,
+    ) {
+        // starving for input doesn't execute anything
+        if *state != ExecutionState::NeedsInput {
+            self.entries.push(TraceEntry {
+                ip,
+                op_code: *instruction.op_code(),
+                reads,
+                writes,
+            });
+        }
+    }
+
+    /// Every input the traced execution consumed, in order
+    pub fn inputs(&self) -> impl Iterator<Item = IntCode> + '_ {
+        self.entries.iter().filter_map(TraceEntry::input)
+    }
+
+    /// Queues our recorded input onto a fresh execution and checks it executes exactly like we did
+    pub fn replay(&self, execution: &mut Execution) -> result::Result<(), Divergence> {
+        execution.input.extend(self.inputs());
+        execution.start_trace();
+
+        let result = self.compare(execution);
+        execution.trace = None;
+
+        result
+    }
+
+    fn compare(&self, execution: &mut Execution) -> result::Result<(), Divergence> {
+        for (index, expected) in self.entries.iter().enumerate() {
+            let state = execution.step().map_err(|error| Divergence::Error {
+                index,
+                expected: Box::new(expected.clone()),
+                error,
+            })?;
+
+            // we only need to look at the latest entry, so don't let the replay trace grow
+            let actual = execution
+                .trace
+                .as_mut()
+                .and_then(|trace| trace.entries.pop());
+
+            match actual {
+                Some(ref actual) if actual == expected => (),
+                Some(actual) => {
+                    return Err(Divergence::Mismatch {
+                        index,
+                        expected: Box::new(expected.clone()),
+                        actual: Box::new(actual),
+                    });
+                }
+                None => {
+                    return Err(Divergence::Stopped {
+                        index,
+                        expected: Box::new(expected.clone()),
+                        state,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I: Input, O: Output> Execution<I, O> {
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace::new());
+    }
+
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::parse_program;
+
+    fn record(program: &str, input: Vec<IntCode>) -> Trace {
+        let mut execution = Execution::new_input(parse_program(program), input);
+        execution.start_trace();
+        execution.run().unwrap();
+
+        execution.take_trace().unwrap()
+    }
+
+    #[test]
+    fn record_trace() {
+        let trace = record("3,9,8,9,10,9,4,9,99,-1,8", vec![8]);
+
+        assert_eq!(
+            trace.to_string(),
+            "    0: IN -> [9]=8 in=8\n    2: EQ 8 8 -> [9]=1\n    6: OUT 1 out=1\n    8: HLT\n"
+        );
+        assert_eq!(trace.inputs().collect::<Vec<_>>(), vec![8]);
+    }
+
+    #[test]
+    fn replay() {
+        let trace = record(
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            vec![],
+        );
+        let mut fresh = Execution::new(parse_program(
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+        ));
+        assert_eq!(trace.replay(&mut fresh), Ok(()));
+        assert_eq!(fresh.output.len(), 16);
+
+        let trace = record("3,9,8,9,10,9,4,9,99,-1,8", vec![8]);
+        let mut modified = Execution::new(parse_program("3,9,8,9,10,9,4,9,99,-1,7"));
+        match trace.replay(&mut modified) {
+            Err(Divergence::Mismatch { index, actual, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(actual.reads, vec![8, 7]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let trace = record("3,9,3,9,99", vec![1, 2]);
+        let mut hungrier = Execution::new(parse_program("3,9,3,9,3,9,99"));
+        match trace.replay(&mut hungrier) {
+            Err(Divergence::Stopped { index, state, .. }) => {
+                assert_eq!(index, 2);
+                assert_eq!(state, ExecutionState::NeedsInput);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -45,8 +45,8 @@ impl Display for Tile {
     }
 }
 
-pub fn read_output(
-    execution: &mut Execution,
+pub fn read_output<I>(
+    execution: &mut Execution<I>,
     screen: &mut Grid<Tile>,
 ) -> (IntCode, IntCode, IntCode) {
     let mut score = 0;
@@ -78,16 +78,22 @@ pub fn read_output(
 mod wasm {
     use super::*;
     use crate::coordinates::CanvasPixel;
-    use crate::cpu::{parse_program, ExecutionState};
+    use crate::cpu::{parse_program, parse_program_with, ExecutionState, ParseOptions};
     use wasm_bindgen::prelude::*;
     use wasm_bindgen::Clamped;
     use wasm_bindgen::__rt::std::collections::VecDeque;
     use wasm_bindgen::{JsCast, JsValue};
     use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
 
+    /// custom programs are pasted in by whoever is playing, so never let one hang the page
+    const INSTRUCTION_BUDGET: usize = 1_000_000;
+    /// nor let one allocate without bound, the real game only needs a few thousand words
+    const MEMORY_LIMIT: usize = 1 << 20;
+
     #[wasm_bindgen]
     pub struct ThirteenGame {
         execution: Execution,
+        state: ExecutionState,
         screen: Grid<Tile>,
         my_winning_game: VecDeque<IntCode>,
         score: i64,
@@ -107,12 +113,19 @@ mod wasm {
                 .as_ref()
                 .map(|x| &**x)
                 .unwrap_or(include_str!("../thirteen/13_input.txt"));
-            let mut paid_program = parse_program(program);
+            let mut paid_program = parse_program_with(program, &ParseOptions { comments: true })
+                .map_err(|e| format!("Parse Error: {}", e))?;
+            if paid_program.is_empty() {
+                return Err(JsValue::from_str("Parse Error: the program is empty"));
+            }
             // pay 2 "quarters" for our game
             paid_program[0] = 2;
 
             let mut execution = Execution::new(paid_program);
-            execution.run().map_err(|e| format!("CPU Error: {:?}", e))?;
+            execution.memory_limit = Some(MEMORY_LIMIT);
+            let state = execution
+                .run_for(INSTRUCTION_BUDGET)
+                .map_err(|e| format!("CPU Error: {:?}", e))?;
 
             let mut screen = Grid::new_from_inclusive_range(0..=44, 0..=44);
 
@@ -139,6 +152,7 @@ mod wasm {
 
             let mut game = ThirteenGame {
                 execution,
+                state,
                 screen,
                 my_winning_game: winning_game_data,
                 score: 0,
@@ -153,17 +167,22 @@ mod wasm {
         }
 
         pub fn step(&mut self, user_input: isize) -> Result<ExecutionState, JsValue> {
-            let input = if let Some(auto_input) = self.my_winning_game.pop_front() {
-                auto_input
-            } else {
-                user_input as i64
-            };
+            // if we ran out of budget last time we're still catching up, so don't queue anything
+            if self.state == ExecutionState::NeedsInput {
+                let input = if let Some(auto_input) = self.my_winning_game.pop_front() {
+                    auto_input
+                } else {
+                    user_input as i64
+                };
+
+                self.execution.input.push_back(input as i64);
+            }
 
-            self.execution.input.push_back(input as i64);
             let state = self
                 .execution
-                .run()
+                .run_for(INSTRUCTION_BUDGET)
                 .map_err(|e| format!("CPU Error: {:?}", e))?;
+            self.state = state.clone();
 
             let (score, _, _) = read_output(&mut self.execution, &mut self.screen);
 